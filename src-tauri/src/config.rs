@@ -0,0 +1,45 @@
+//! Small JSON settings files in the app config directory
+//!
+//! Each feature keeps its preferences in a file of its own, such as
+//! `logging.json` or `updater.json`, rather than in the app database, so
+//! they are still there when the database fails to open.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+fn path(app: &AppHandle, file_name: &str) -> io::Result<PathBuf> {
+    app.path()
+        .app_config_dir()
+        .map(|dir| dir.join(file_name))
+        .map_err(|e| io::Error::other(format!("Failed to find the config directory: {}", e)))
+}
+
+/// Read a settings file, falling back to defaults if it is missing or unreadable
+pub fn load<T: DeserializeOwned + Default>(app: &AppHandle, file_name: &str) -> T {
+    let Ok(path) = path(app, file_name) else {
+        return T::default();
+    };
+
+    match fs::read(&path) {
+        Ok(contents) => serde_json::from_slice(&contents).unwrap_or_else(|e| {
+            log::warn!("Ignoring invalid settings in {}: {}", path.display(), e);
+            T::default()
+        }),
+        Err(_) => T::default(),
+    }
+}
+
+/// Write a settings file, creating the config directory if needed
+pub fn save<T: Serialize>(app: &AppHandle, file_name: &str, settings: &T) -> io::Result<()> {
+    let path = path(app, file_name)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let contents = serde_json::to_vec_pretty(settings)?;
+    fs::write(&path, contents)
+}
@@ -1,103 +1,2445 @@
-use tauri::State;
+use base64::Engine;
+use futures_util::TryStreamExt;
 use serde::{Deserialize, Serialize};
-use std::sync::{Mutex, PoisonError};
+use sqlx::sqlite::{
+    SqliteConnectOptions, SqliteJournalMode, SqliteRow, SqliteSynchronous, SqliteValueRef,
+};
+use sqlx::{Column, Either, Executor, Row, Statement, TypeInfo, ValueRef};
+use std::collections::hash_map::Entry;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+pub mod attach;
+pub mod backup;
+pub mod bulk;
+pub mod cursor;
+pub mod dump;
+mod error;
+pub mod fts;
+mod functions;
+pub mod import;
+pub mod integrity;
+pub mod migrations;
+pub mod query_log;
+pub mod replica;
+pub mod schema;
+pub mod setup;
+mod sql;
+pub mod vacuum;
+
+use error::sqlite_code;
+pub use error::DbError;
 
 // We'll store database connections in Tauri's managed state
 pub struct DbState {
     // Map of connection URLs to their instances
     pub connections: Mutex<std::collections::HashMap<String, sqlx::SqlitePool>>,
+    // Databases each pool attaches on every connection, by connection URL
+    pub attachments: Mutex<std::collections::HashMap<String, Arc<attach::Attachments>>>,
+    // Statements run on every new connection, by connection URL; kept when pools close
+    pub setup_statements: Mutex<std::collections::HashMap<String, Arc<[String]>>>,
+    // Cancel flags of running transactions, by caller-chosen transaction id
+    pub cancellations: Mutex<std::collections::HashMap<String, Arc<AtomicBool>>>,
+    // Recent statements run by execute_transaction, when turned on
+    pub query_log: query_log::QueryLog,
+    // Normalized form of each connection URL seen, so paths are resolved once
+    pub pool_keys: Mutex<std::collections::HashMap<String, String>>,
+    // Replica URL that read-only transactions use instead, by connection URL
+    pub read_replicas: Mutex<std::collections::HashMap<String, String>>,
+    // Keys of pools borrowed from tauri-plugin-sql, which only the plugin may close
+    pub shared_pools: Mutex<std::collections::HashSet<String>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct TransactionStep {
     pub sql: String,
+    #[serde(default)]
     pub params: Vec<serde_json::Value>,
+    /// Values for `:name` / `@name` / `$name` placeholders, used instead of `params`
+    #[serde(default)]
+    pub named_params: Option<serde_json::Map<String, serde_json::Value>>,
+    /// Run the step inside its own SAVEPOINT so a failure only rolls back this step
+    ///
+    /// This relaxes the all-or-nothing guarantee for flagged steps only: a failed
+    /// savepoint step is recorded in its StepResult and the transaction carries on
+    /// and commits. Unflagged steps still abort the whole transaction on failure.
+    #[serde(default)]
+    pub savepoint: bool,
+    /// Fail with a conflict error, rolling back, unless the step changes exactly this many rows
+    ///
+    /// For optimistic concurrency: an `UPDATE ... WHERE id = ? AND version = ?`
+    /// that matches nothing means another writer got there first.
+    #[serde(default)]
+    pub expect_rows_affected: Option<u64>,
+}
+
+/// A step with its parameters resolved to positional form
+struct ResolvedStep {
+    sql: String,
+    params: Vec<serde_json::Value>,
+    savepoint: bool,
+    expect_rows_affected: Option<u64>,
+}
+
+impl TransactionStep {
+    /// Resolve the step into SQL with positional placeholders and the values to bind
+    fn resolve(self) -> Result<ResolvedStep, DbError> {
+        let Some(mut named_params) = self.named_params else {
+            return Ok(ResolvedStep {
+                sql: self.sql,
+                params: self.params,
+                savepoint: self.savepoint,
+                expect_rows_affected: self.expect_rows_affected,
+            });
+        };
+
+        if !self.params.is_empty() {
+            return Err(DbError::bind(
+                "A step cannot use both params and namedParams".to_string(),
+            ));
+        }
+
+        let (sql, names) = sql::rewrite_named_params(&self.sql).map_err(DbError::bind)?;
+
+        let missing: Vec<&str> = names
+            .iter()
+            .filter(|name| !named_params.contains_key(name.as_str()))
+            .map(|name| name.as_str())
+            .collect();
+        if !missing.is_empty() {
+            return Err(DbError::bind(format!(
+                "Missing named parameters: {}",
+                missing.join(", ")
+            )));
+        }
+
+        let params = names
+            .iter()
+            .filter_map(|name| named_params.remove(name))
+            .collect();
+
+        Ok(ResolvedStep {
+            sql,
+            params,
+            savepoint: self.savepoint,
+            expect_rows_affected: self.expect_rows_affected,
+        })
+    }
+}
+
+/// Connection pool settings; unset fields keep the defaults
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolOptions {
+    /// Ignored for in-memory databases, which always use a single connection
+    pub max_connections: Option<u32>,
+    pub acquire_timeout_ms: Option<u64>,
+    /// SQLite journal mode ("wal", "delete", "truncate", "persist", "memory", "off"); defaults
+    /// to WAL, except read-only pools keep the file's current mode
+    pub journal_mode: Option<String>,
+    /// Enforce foreign key constraints; defaults to true
+    pub foreign_keys: Option<bool>,
+    /// How long a connection waits on a locked database before failing
+    pub busy_timeout_ms: Option<u64>,
+    /// SQLCipher passphrase for an encrypted database; requires the `sqlcipher` feature
+    pub passphrase: Option<String>,
+    /// Open connections read-only so any write fails; overrides `mode=` in the URL
+    pub read_only: Option<bool>,
+    /// Prepared statements each connection keeps, keyed by SQL text; defaults to 100
+    ///
+    /// Steps, queries and batches reuse a cached statement when the same SQL
+    /// runs again on a connection, so repeated parameterized statements are
+    /// only prepared once. Raise this if more distinct statements than that
+    /// are repeated, such as during a large import.
+    pub statement_cache_capacity: Option<usize>,
+    /// Named defaults for synchronous and cache_size; fields set here override it
+    pub preset: Option<PoolPreset>,
+    /// SQLite synchronous mode ("off", "normal", "full", "extra"); defaults to full
+    ///
+    /// FULL syncs to disk on every commit, so a committed transaction survives
+    /// a power failure or OS crash. NORMAL syncs less often: in WAL mode the
+    /// database can't be corrupted, but the last transactions committed before
+    /// a power failure or OS crash may be rolled back (an app crash loses
+    /// nothing). Outside WAL mode NORMAL also carries a small risk of
+    /// corruption on power loss, so only use it with WAL. OFF hands writes to
+    /// the OS without syncing at all.
+    pub synchronous: Option<String>,
+    /// Page cache size per connection, as PRAGMA cache_size takes it: a
+    /// positive number of pages, or a negative size in KiB; SQLite's default
+    /// is -2000 (about 2 MB)
+    pub cache_size: Option<i64>,
+}
+
+/// Ready-made synchronous and cache_size settings
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PoolPreset {
+    /// synchronous FULL with SQLite's default cache: every commit is on disk
+    /// before it returns
+    Safe,
+    /// synchronous NORMAL with a 64 MB cache, for bulk writes into a WAL
+    /// database; commits made just before a power failure or OS crash can be
+    /// lost, though the database stays intact
+    FastImport,
+}
+
+impl PoolPreset {
+    fn synchronous(self) -> SqliteSynchronous {
+        match self {
+            PoolPreset::Safe => SqliteSynchronous::Full,
+            PoolPreset::FastImport => SqliteSynchronous::Normal,
+        }
+    }
+
+    fn cache_size(self) -> Option<i64> {
+        match self {
+            PoolPreset::Safe => None,
+            PoolPreset::FastImport => Some(-64 * 1024),
+        }
+    }
+}
+
+/// Default time a connection waits on a locked database
+const DEFAULT_BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Per-call settings for execute_transaction
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionOptions {
+    /// How many times to retry the transaction when the database is busy; defaults to 5
+    pub busy_retries: Option<u32>,
+    /// Abort and roll back if the transaction (including retries) takes longer than this
+    pub timeout_ms: Option<u64>,
+    /// How the transaction acquires its locks; defaults to deferred
+    #[serde(default)]
+    pub tx_mode: TransactionMode,
+    /// Event to emit TransactionProgress on; defaults to "db-transaction-progress"
+    pub progress_event_name: Option<String>,
+    /// Run every step as usual, then roll back instead of committing
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Measure how long each step takes, reported as StepResult::duration_micros
+    #[serde(default)]
+    pub timing: bool,
+    /// Run the steps concurrently on separate pooled connections, outside a transaction
+    ///
+    /// Only for steps that all read: SELECT, VALUES, or WITH queries that
+    /// don't write; any other step fails the call before anything runs, as
+    /// does a savepoint step. Without a transaction each step sees whatever
+    /// was committed when it started, so a write from elsewhere can land
+    /// between two of them. Results are still in step order. busyRetries and
+    /// txMode don't apply.
+    #[serde(default)]
+    pub parallel_reads: bool,
+}
+
+/// SQLite transaction behavior, chosen by the BEGIN statement
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionMode {
+    /// Take locks on first read or write; conflicts surface mid-transaction
+    #[default]
+    Deferred,
+    /// Take the write lock up front, so busy errors happen at BEGIN
+    Immediate,
+    /// Like immediate, and also block readers outside WAL mode
+    Exclusive,
+}
+
+impl TransactionMode {
+    fn begin_sql(self) -> &'static str {
+        match self {
+            TransactionMode::Deferred => "BEGIN DEFERRED",
+            TransactionMode::Immediate => "BEGIN IMMEDIATE",
+            TransactionMode::Exclusive => "BEGIN EXCLUSIVE",
+        }
+    }
+}
+
+const DEFAULT_BUSY_RETRIES: u32 = 5;
+const BUSY_RETRY_BASE_DELAY: Duration = Duration::from_millis(50);
+const BUSY_RETRY_MAX_DELAY: Duration = Duration::from_secs(2);
+
+/// Event execute_transaction reports progress on unless the caller names one
+const DEFAULT_PROGRESS_EVENT: &str = "db-transaction-progress";
+/// Steps between progress events
+const PROGRESS_INTERVAL: usize = 100;
+
+/// Settings shared by every attempt of one execute_transaction call
+struct AttemptOptions<'a> {
+    mode: TransactionMode,
+    dry_run: bool,
+    timing: bool,
+    progress: Option<&'a ProgressReporter>,
+    cancel: Option<&'a AtomicBool>,
+    query_log: Option<query_log::Recorder<'a>>,
+    /// Steps the current attempt has run, still readable after a timeout or
+    /// a failed commit
+    completed: &'a AtomicUsize,
+}
+
+/// Why a transaction attempt failed
+enum AttemptError {
+    Failed(DbError),
+    /// cancel_transaction was called; the attempt was rolled back after
+    /// running `completed_steps` steps
+    Cancelled {
+        completed_steps: usize,
+    },
+}
+
+impl From<DbError> for AttemptError {
+    fn from(error: DbError) -> Self {
+        AttemptError::Failed(error)
+    }
+}
+
+/// Outcome of execute_batch
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchResult {
+    pub success: bool,
+    pub error: Option<String>,
+    /// The failure with its kind and SQLite result code
+    pub error_detail: Option<DbError>,
+    /// Number of statements that ran successfully
+    pub statements_executed: usize,
+    /// Text of the statement that failed, if any
+    pub failed_statement: Option<String>,
+}
+
+/// Payload of execute_transaction progress events
+#[derive(Debug, Clone, Serialize)]
+pub struct TransactionProgress {
+    pub completed: usize,
+    pub total: usize,
+}
+
+/// Emits progress events for one transaction
+struct ProgressReporter {
+    app: AppHandle,
+    event: String,
+}
+
+impl ProgressReporter {
+    fn step_completed(&self, completed: usize, total: usize) {
+        if completed % PROGRESS_INTERVAL == 0 || completed == total {
+            let _ = self
+                .app
+                .emit(&self.event, TransactionProgress { completed, total });
+        }
+    }
+}
+
+/// Event emitted with DbChanged after a write is committed
+const DB_CHANGED_EVENT: &str = "db-changed";
+
+/// Payload of db-changed events, so other windows can refresh what they show
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DbChanged {
+    pub db_url: String,
+    /// Tables the committed statements wrote to or altered, as named in the SQL
+    pub tables: Vec<String>,
+}
+
+/// Emit db-changed for the tables written by `statements`, if there are any
+fn notify_db_changed<'a>(
+    app: &AppHandle,
+    db_url: &str,
+    statements: impl IntoIterator<Item = &'a str>,
+) {
+    let mut tables: Vec<String> = Vec::new();
+    for table in statements.into_iter().filter_map(sql::written_table) {
+        if !tables.contains(&table) {
+            tables.push(table);
+        }
+    }
+    notify_tables_changed(app, db_url, tables);
+}
+
+/// Emit db-changed for `tables`, if there are any
+fn notify_tables_changed(app: &AppHandle, db_url: &str, tables: Vec<String>) {
+    if tables.is_empty() {
+        return;
+    }
+
+    let _ = app.emit(
+        DB_CHANGED_EVENT,
+        DbChanged {
+            db_url: db_url.to_string(),
+            tables,
+        },
+    );
+}
+
+/// Snapshot of an open pool, for debugging
+#[derive(Debug, Serialize)]
+pub struct ConnectionInfo {
+    /// The normalized URL the pool is stored under, with " (read-only)" for a read-only pool
+    pub url: String,
+    pub size: u32,
+    pub idle: usize,
+}
+
+/// Result of pinging one open pool
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PingResult {
+    /// The normalized URL the pool is stored under, as in list_connections
+    pub url: String,
+    pub ok: bool,
+    /// How long `SELECT 1` took, or how long the ping waited before giving up
+    pub latency_ms: f64,
+    pub error: Option<String>,
+    /// The pool failed and was replaced; the next command for it reconnects
+    pub evicted: bool,
+}
+
+/// One node of an EXPLAIN QUERY PLAN tree
+#[derive(Debug, Serialize)]
+pub struct QueryPlanStep {
+    pub id: i64,
+    /// id of the parent node, or 0 at the top level
+    pub parent: i64,
+    pub detail: String,
+}
+
+/// Connection counts of one pool, for monitoring contention
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolStats {
+    /// Open connections, both idle and in use
+    pub size: u32,
+    pub idle: usize,
+    pub max_connections: u32,
 }
 
 #[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct TransactionResult {
     pub success: bool,
     pub error: Option<String>,
+    /// The failure with its kind and SQLite result code, when a step failed
+    pub error_detail: Option<DbError>,
+    /// Rows produced by each step, one entry per step (empty for non-SELECT steps)
+    pub results: Vec<Vec<serde_json::Value>>,
+    /// Column names for each step's rows, one entry per step
+    pub columns: Vec<Vec<String>>,
+    /// Declared type of each column, parallel to `columns`: "INTEGER", "REAL",
+    /// "TEXT", "BLOB", "BOOLEAN", "DATE", "TIME" or "DATETIME", and "NULL" for
+    /// expressions and columns declared without a recognised type
+    pub column_types: Vec<Vec<String>>,
+    /// Write statistics for each step, one entry per step
+    pub steps: Vec<StepResult>,
+    /// Zero-based index of the step that failed; the transaction was rolled back
+    pub failed_step: Option<usize>,
+    /// How many steps ran before the transaction committed, failed or was
+    /// cancelled; every step on success. A failed or cancelled transaction is
+    /// still rolled back in full, so this is only for reporting.
+    pub completed_steps: usize,
+    /// The transaction was cancelled through cancel_transaction and rolled back
+    pub cancelled: bool,
 }
 
-fn handle_poison_error<T>(_e: PoisonError<T>) -> String {
-    "Internal error: state corrupted".to_string()
+impl TransactionResult {
+    fn failed(failed_step: Option<usize>, completed_steps: usize, error: DbError) -> Self {
+        TransactionResult {
+            success: false,
+            error: Some(error.to_string()),
+            error_detail: Some(error),
+            results: Vec::new(),
+            columns: Vec::new(),
+            column_types: Vec::new(),
+            steps: Vec::new(),
+            failed_step,
+            completed_steps,
+            cancelled: false,
+        }
+    }
+
+    fn cancelled(completed_steps: usize, error: String) -> Self {
+        TransactionResult {
+            success: false,
+            error: Some(error),
+            error_detail: None,
+            results: Vec::new(),
+            columns: Vec::new(),
+            column_types: Vec::new(),
+            steps: Vec::new(),
+            failed_step: None,
+            completed_steps,
+            cancelled: true,
+        }
+    }
 }
 
-/// Execute multiple SQL statements in a transaction
-#[tauri::command]
-pub async fn execute_transaction(
-    db_url: String,
-    steps: Vec<TransactionStep>,
-    state: State<'_, DbState>,
-) -> Result<TransactionResult, String> {
-    // Check if pool exists (without awaiting inside lock)
-    let pool = {
-        let connections_guard = state.connections.lock().map_err(handle_poison_error)?;
-        connections_guard.get(&db_url).cloned()
+/// Keeps a transaction's cancel flag registered while it runs
+struct CancelRegistration<'a> {
+    state: &'a DbState,
+    transaction_id: String,
+    flag: Arc<AtomicBool>,
+}
+
+impl<'a> CancelRegistration<'a> {
+    fn new(state: &'a DbState, transaction_id: String) -> Result<Self, DbError> {
+        let flag = Arc::new(AtomicBool::new(false));
+        let mut cancellations = state.cancellations.lock().map_err(handle_poison_error)?;
+        match cancellations.entry(transaction_id.clone()) {
+            Entry::Occupied(_) => {
+                return Err(DbError::Invalid(format!(
+                    "Transaction already running: {}",
+                    transaction_id
+                )))
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(flag.clone());
+            }
+        }
+
+        Ok(CancelRegistration {
+            state,
+            transaction_id,
+            flag,
+        })
+    }
+}
+
+impl Drop for CancelRegistration<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut cancellations) = self.state.cancellations.lock() {
+            cancellations.remove(&self.transaction_id);
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StepResult {
+    /// False only for a savepoint step that failed and was rolled back
+    pub success: bool,
+    pub error: Option<String>,
+    /// The failure with its kind and SQLite result code, for a failed savepoint step
+    pub error_detail: Option<DbError>,
+    pub rows_affected: u64,
+    /// Rowid assigned by an INSERT/REPLACE step, if it inserted anything
+    pub last_insert_rowid: Option<i64>,
+    /// Time the step took to prepare, run and read its rows; only measured with `timing`
+    pub duration_micros: Option<u64>,
+}
+
+fn handle_poison_error<T>(_e: PoisonError<T>) -> DbError {
+    DbError::Internal("Internal error: state corrupted".to_string())
+}
+
+type SqliteQuery<'q> = sqlx::query::Query<'q, sqlx::Sqlite, sqlx::sqlite::SqliteArguments<'q>>;
+
+/// Build the per-connection settings for a database URL
+fn connect_options(db_url: &str, options: &PoolOptions) -> Result<SqliteConnectOptions, DbError> {
+    let read_only = options.read_only.unwrap_or(false);
+    let journal_mode = match &options.journal_mode {
+        Some(mode) => Some(
+            SqliteJournalMode::from_str(mode)
+                .map_err(|_| DbError::Invalid(format!("Unknown journal mode: {}", mode)))?,
+        ),
+        // Switching to WAL is a write, so read-only pools keep the file's mode
+        None if read_only => None,
+        None => Some(SqliteJournalMode::Wal),
+    };
+
+    let mut connect_options = SqliteConnectOptions::from_str(db_url)
+        .map_err(|e| DbError::Invalid(format!("Invalid database URL: {}", e)))?
+        .foreign_keys(options.foreign_keys.unwrap_or(true))
+        .read_only(read_only)
+        .busy_timeout(
+            options
+                .busy_timeout_ms
+                .map(Duration::from_millis)
+                .unwrap_or(DEFAULT_BUSY_TIMEOUT),
+        );
+    if let Some(journal_mode) = journal_mode {
+        connect_options = connect_options.journal_mode(journal_mode);
+    }
+    if let Some(capacity) = options.statement_cache_capacity {
+        connect_options = connect_options.statement_cache_capacity(capacity);
+    }
+
+    // Both are per-connection settings, which sqlx applies to every
+    // connection the pool opens
+    let synchronous = match &options.synchronous {
+        Some(mode) => Some(
+            SqliteSynchronous::from_str(mode)
+                .map_err(|_| DbError::Invalid(format!("Unknown synchronous mode: {}", mode)))?,
+        ),
+        None => options.preset.map(PoolPreset::synchronous),
+    };
+    if let Some(synchronous) = synchronous {
+        connect_options = connect_options.synchronous(synchronous);
+    }
+    if let Some(cache_size) = options
+        .cache_size
+        .or_else(|| options.preset.and_then(PoolPreset::cache_size))
+    {
+        connect_options = connect_options.pragma("cache_size", cache_size.to_string());
+    }
+
+    // sqlx issues `key` before any other pragma on every new connection,
+    // which is what SQLCipher requires
+    let connect_options = match &options.passphrase {
+        Some(passphrase) => {
+            require_sqlcipher()?;
+            connect_options.pragma("key", quote_literal(passphrase))
+        }
+        None => connect_options,
     };
 
-    // Get or create pool
-    let pool = if let Some(existing_pool) = pool {
-        existing_pool
+    Ok(connect_options)
+}
+
+/// Fail unless this build links SQLCipher instead of plain SQLite
+fn require_sqlcipher() -> Result<(), DbError> {
+    if cfg!(feature = "sqlcipher") {
+        Ok(())
     } else {
-        // Create new pool outside of lock
-        let new_pool = sqlx::SqlitePool::connect(&db_url)
-            .await
-            .map_err(|e| format!("Failed to connect to database: {}", e))?;
-        
-        // Store it
-        {
-            let mut connections_guard = state.connections.lock().map_err(handle_poison_error)?;
-            connections_guard.insert(db_url.clone(), new_pool.clone());
+        Err(DbError::Unsupported(
+            "Encrypted databases require a build with the sqlcipher feature".to_string(),
+        ))
+    }
+}
+
+/// Quote text as an SQL string literal
+fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Whether a URL names an in-memory or temporary database
+///
+/// Each connection to such a URL can get a database of its own, which is
+/// gone once that connection closes.
+fn is_in_memory(db_url: &str) -> bool {
+    let url = db_url
+        .trim_start_matches("sqlite:")
+        .trim_start_matches("//");
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    path.is_empty()
+        || path == ":memory:"
+        || path.starts_with("file::memory:")
+        || query.split('&').any(|param| param == "mode=memory")
+}
+
+/// Normalize a database URL so equivalent spellings of it share one pool
+///
+/// The path is made absolute with symlinks resolved, through its directory
+/// if the file doesn't exist yet, and query parameters are sorted: so
+/// `sqlite:app.db?mode=rwc&cache=shared` and
+/// `sqlite:./app.db?cache=shared&mode=rwc` give the same key. In-memory URLs
+/// keep their path, since it names a database rather than a file.
+fn normalize_db_url(db_url: &str) -> String {
+    let url = db_url.strip_prefix("sqlite:").unwrap_or(db_url);
+    let url = url.strip_prefix("//").unwrap_or(url);
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+
+    let path = if is_in_memory(db_url) {
+        path.to_string()
+    } else {
+        let file = std::path::Path::new(path);
+        let resolved = std::fs::canonicalize(file).ok().or_else(|| {
+            let parent = match file.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent,
+                _ => std::path::Path::new("."),
+            };
+            let resolved_parent = std::fs::canonicalize(parent).ok()?;
+            Some(resolved_parent.join(file.file_name()?))
+        });
+        match resolved {
+            // Windows resolves to a verbatim \\?\C:\ path, which nobody writes by hand
+            Some(resolved) => {
+                let resolved = resolved.to_string_lossy();
+                resolved
+                    .strip_prefix(r"\\?\")
+                    .filter(|rest| !rest.starts_with(r"UNC\"))
+                    .unwrap_or(&resolved)
+                    .to_string()
+            }
+            None => path.to_string(),
         }
-        
-        new_pool
     };
 
-    // Ensure foreign keys are enforced for this connection
-    sqlx::query("PRAGMA foreign_keys = ON")
-        .execute(&pool)
-        .await
-        .map_err(|e| format!("Failed to enable foreign keys: {}", e))?;
+    let mut params: Vec<&str> = query.split('&').filter(|param| !param.is_empty()).collect();
+    params.sort_unstable();
+    if params.is_empty() {
+        format!("sqlite:{}", path)
+    } else {
+        format!("sqlite:{}?{}", path, params.join("&"))
+    }
+}
 
-    // Begin transaction
-    let mut tx = pool.begin().await.map_err(|e| format!("Failed to begin transaction: {}", e))?;
+/// The file a database URL refers to
+///
+/// Fails for in-memory databases, for URLs other than `sqlite:` ones, and if
+/// the file doesn't exist.
+#[cfg(desktop)]
+fn db_file_path(db_url: &str) -> Result<std::path::PathBuf, DbError> {
+    if !db_url.starts_with("sqlite:") {
+        return Err(DbError::Invalid(format!(
+            "{} is not an SQLite database URL",
+            db_url
+        )));
+    }
+    if is_in_memory(db_url) {
+        return Err(DbError::Invalid(format!(
+            "{} is an in-memory database and has no file",
+            db_url
+        )));
+    }
 
-    // Execute all steps
-    for step in steps {
-        let mut query = sqlx::query(&step.sql);
-        
-        // Bind parameters
-        for param in step.params {
-            query = match param {
-                serde_json::Value::String(s) => query.bind(s),
-                serde_json::Value::Number(n) => {
-                    if let Some(i) = n.as_i64() {
-                        query.bind(i)
-                    } else if let Some(f) = n.as_f64() {
-                        query.bind(f)
-                    } else {
-                        return Err("Invalid number type".to_string());
-                    }
+    let normalized = normalize_db_url(db_url);
+    let url = normalized.trim_start_matches("sqlite:");
+    let path = url.split_once('?').map_or(url, |(path, _)| path);
+    let path = std::path::PathBuf::from(path.strip_prefix("file:").unwrap_or(path));
+    if !path.is_file() {
+        return Err(DbError::Invalid(format!(
+            "Database file {} does not exist",
+            path.display()
+        )));
+    }
+    Ok(path)
+}
+
+/// Key the pools, attachments and setup statements for a database URL are stored under
+fn pool_key(state: &DbState, db_url: &str) -> Result<String, DbError> {
+    if let Some(key) = state
+        .pool_keys
+        .lock()
+        .map_err(handle_poison_error)?
+        .get(db_url)
+    {
+        return Ok(key.clone());
+    }
+
+    // Resolve outside the lock; it touches the filesystem
+    let key = normalize_db_url(db_url);
+    state
+        .pool_keys
+        .lock()
+        .map_err(handle_poison_error)?
+        .insert(db_url.to_string(), key.clone());
+    Ok(key)
+}
+
+/// Open a new pool for a database URL
+///
+/// An in-memory database gets a pool of one connection that stays open until
+/// the pool is closed, so every command sees the same database and its data
+/// lasts as long as the pool. While a cursor holds that connection, other
+/// commands on the database wait for it to be closed.
+async fn create_pool(
+    db_url: &str,
+    options: &PoolOptions,
+    attachments: Arc<attach::Attachments>,
+    setup: Arc<[String]>,
+) -> Result<sqlx::SqlitePool, DbError> {
+    let connect_options = connect_options(db_url, options)?;
+    let (setup_failure, setup_failed) = setup::SetupFailure::new();
+    let setup_failure = Arc::new(setup_failure);
+
+    // Replacing the one connection of an in-memory database would lose its
+    // data, so attach_database changes that connection in place instead
+    let in_memory = is_in_memory(db_url);
+    let connect_attachments = attachments.clone();
+    let mut pool_options = sqlx::sqlite::SqlitePoolOptions::new()
+        .after_connect(move |conn, _meta| {
+            let attachments = connect_attachments.clone();
+            let setup = setup.clone();
+            let setup_failure = setup_failure.clone();
+            Box::pin(async move {
+                functions::register_functions(conn).await?;
+                setup::run_setup(conn, &setup, &setup_failure).await?;
+                attachments.attach_all(conn).await
+            })
+        })
+        .before_acquire(move |_conn, meta| {
+            let current = in_memory || attachments.is_current(&meta);
+            Box::pin(async move { Ok(current) })
+        });
+    if in_memory {
+        if options.max_connections.is_some_and(|max| max > 1) {
+            log::warn!(
+                "Ignoring maxConnections for in-memory database {}; it uses one connection",
+                db_url
+            );
+        }
+        pool_options = pool_options
+            .max_connections(1)
+            .min_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None);
+    } else if let Some(max_connections) = options.max_connections {
+        pool_options = pool_options.max_connections(max_connections);
+    }
+    if let Some(acquire_timeout_ms) = options.acquire_timeout_ms {
+        pool_options = pool_options.acquire_timeout(Duration::from_millis(acquire_timeout_ms));
+    }
+
+    tokio::select! {
+        pool = pool_options.connect_with(connect_options) => {
+            pool.map_err(|e| DbError::connect("Failed to connect to database", e))
+        }
+        Ok(error) = setup_failed => Err(error),
+    }
+}
+
+/// Look up the pool for a database URL, creating and storing it on first use
+async fn get_or_create_pool(state: &DbState, db_url: &str) -> Result<sqlx::SqlitePool, DbError> {
+    let key = pool_key(state, db_url)?;
+    get_or_create_pool_as(state, &key, &key, db_url, &PoolOptions::default()).await
+}
+
+/// Appended to a database URL to key its read-only pool
+const READ_ONLY_SUFFIX: &str = " (read-only)";
+
+/// Key the read-only pool for a database URL is stored under
+fn read_only_key(db_url: &str) -> String {
+    format!("{}{}", db_url, READ_ONLY_SUFFIX)
+}
+
+/// Look up the read-only pool for a database URL, creating it on first use
+///
+/// This is a separate pool from the one other commands use for the URL.
+async fn get_or_create_read_only_pool(
+    state: &DbState,
+    db_url: &str,
+) -> Result<sqlx::SqlitePool, DbError> {
+    // A second pool would open a second, empty database
+    if is_in_memory(db_url) {
+        return Err(DbError::Unsupported(
+            "Read-only queries aren't supported on in-memory databases".to_string(),
+        ));
+    }
+
+    let options = PoolOptions {
+        read_only: Some(true),
+        ..PoolOptions::default()
+    };
+    let key = pool_key(state, db_url)?;
+    get_or_create_pool_as(state, &read_only_key(&key), &key, db_url, &options).await
+}
+
+/// Look up the pool stored under `key`, opening `db_url` with `options` on first use
+///
+/// `url_key` is the pool_key of `db_url`, which its attachments and setup
+/// statements are registered under.
+async fn get_or_create_pool_as(
+    state: &DbState,
+    key: &str,
+    url_key: &str,
+    db_url: &str,
+    options: &PoolOptions,
+) -> Result<sqlx::SqlitePool, DbError> {
+    // Check if pool exists (without awaiting inside lock)
+    let pool = {
+        let connections_guard = state.connections.lock().map_err(handle_poison_error)?;
+        connections_guard.get(key).cloned()
+    };
+
+    if let Some(existing_pool) = pool {
+        return Ok(existing_pool);
+    }
+
+    // Create new pool outside of lock
+    let attachments = attach::pool_attachments(state, url_key)?;
+    let setup = setup::setup_statements(state, url_key)?;
+    let new_pool = create_pool(db_url, options, attachments, setup).await?;
+
+    // Store it
+    {
+        let mut connections_guard = state.connections.lock().map_err(handle_poison_error)?;
+        connections_guard.insert(key.to_string(), new_pool.clone());
+    }
+
+    Ok(new_pool)
+}
+
+/// Look up the pool for a database URL that must already be open
+fn get_open_pool(state: &DbState, db_url: &str) -> Result<sqlx::SqlitePool, DbError> {
+    let key = pool_key(state, db_url)?;
+    let connections_guard = state.connections.lock().map_err(handle_poison_error)?;
+    connections_guard
+        .get(&key)
+        .cloned()
+        .ok_or_else(|| DbError::Invalid(format!("Database is not open: {}", db_url)))
+}
+
+/// Whether a failure means the pool can't be used again and should be reopened
+///
+/// That's the case once the database file became unusable, or the pool was closed.
+fn is_broken_pool(pool: &sqlx::SqlitePool, error: &DbError) -> bool {
+    error.is_connection_error() || pool.is_closed()
+}
+
+/// Replace a pool whose connections broke with a fresh one with the same settings
+///
+/// The new pool connects on first use, so the next command on the database
+/// reopens the file; its attachments, functions and setup statements are
+/// applied again as it connects. The old pool is closed once the
+/// connections still checked out of it are returned.
+///
+/// A pool shared with tauri-plugin-sql belongs to the plugin, so it is kept
+/// as it is. Returns whether the pool was replaced.
+fn reopen_pool(state: &DbState, db_url: &str, broken: &sqlx::SqlitePool) -> Result<bool, DbError> {
+    let key = pool_key(state, db_url)?;
+    replace_pool(state, &key, broken)
+}
+
+/// reopen_pool for the pool stored under `key`
+fn replace_pool(state: &DbState, key: &str, broken: &sqlx::SqlitePool) -> Result<bool, DbError> {
+    if state
+        .shared_pools
+        .lock()
+        .map_err(handle_poison_error)?
+        .contains(key)
+    {
+        log::warn!(
+            "Keeping the tauri-plugin-sql pool for {} after a connection error",
+            key
+        );
+        return Ok(false);
+    }
+
+    let fresh = broken
+        .options()
+        .clone()
+        .connect_lazy_with((*broken.connect_options()).clone());
+    state
+        .connections
+        .lock()
+        .map_err(handle_poison_error)?
+        .insert(key.to_string(), fresh);
+
+    log::warn!("Reopening the pool for {} after a connection error", key);
+    let broken = broken.clone();
+    tauri::async_runtime::spawn(async move { broken.close().await });
+    Ok(true)
+}
+
+/// Remove the pools for a database URL from state, along with their attachments
+///
+/// Returns the pools that were open: the main one and its read-only twin.
+fn forget_pools(state: &DbState, db_url: &str) -> Result<Vec<sqlx::SqlitePool>, DbError> {
+    let key = pool_key(state, db_url)?;
+    let keys = [read_only_key(&key), key];
+
+    let mut attachments = state.attachments.lock().map_err(handle_poison_error)?;
+    let mut shared_pools = state.shared_pools.lock().map_err(handle_poison_error)?;
+    let mut connections_guard = state.connections.lock().map_err(handle_poison_error)?;
+    Ok(keys
+        .iter()
+        .filter_map(|key| {
+            attachments.remove(key);
+            shared_pools.remove(key);
+            connections_guard.remove(key)
+        })
+        .collect())
+}
+
+/// Bind JSON parameters to a query positionally
+///
+/// SQLite integers are signed 64-bit, so whole numbers above `i64::MAX` are
+/// bound as their exact decimal TEXT rather than rounded through f64. Store
+/// such values in TEXT columns: INTEGER affinity would convert them to REAL.
+///
+/// JSON null binds SQL NULL. A NULL has no storage class of its own, whatever
+/// Rust type it is bound through: `typeof(?)` is 'null', comparisons with it
+/// are NULL and STRICT tables accept it in any nullable column. The type tags
+/// also take null, so a nullable value can keep its tag.
+///
+/// Errors name the zero-based index of the parameter that couldn't be bound.
+fn bind_params<'q>(
+    mut query: SqliteQuery<'q>,
+    params: Vec<serde_json::Value>,
+) -> Result<SqliteQuery<'q>, DbError> {
+    for (index, param) in params.into_iter().enumerate() {
+        let bound = match param {
+            serde_json::Value::String(s) => Ok(query.bind(s)),
+            serde_json::Value::Number(n) => {
+                if let Some(i) = n.as_i64() {
+                    Ok(query.bind(i))
+                } else if let Some(u) = n.as_u64() {
+                    Ok(query.bind(u.to_string()))
+                } else if let Some(f) = n.as_f64() {
+                    Ok(query.bind(f))
+                } else {
+                    Err(format!("invalid number {}", n))
                 }
-                serde_json::Value::Bool(b) => query.bind(b),
-                serde_json::Value::Null => query.bind(None::<String>),
-                _ => return Err("Unsupported parameter type".to_string()),
+            }
+            serde_json::Value::Bool(b) => Ok(query.bind(b)),
+            serde_json::Value::Null => Ok(query.bind(None::<String>)),
+            serde_json::Value::Object(map) => bind_tagged(query, map),
+            serde_json::Value::Array(_) => {
+                Err("unsupported parameter type array (wrap it as {\"$json\": ...})".to_string())
+            }
+        };
+        query =
+            bound.map_err(|message| DbError::bind(format!("parameter {}: {}", index, message)))?;
+    }
+
+    Ok(query)
+}
+
+/// Bind a tagged parameter object such as `{"$blob": "<base64>"}`
+///
+/// Tagged objects have exactly one key naming how the value should be bound.
+/// `{"$datetime": "<RFC 3339>"}` is converted to UTC and stored as RFC 3339
+/// text (`2024-01-02T03:04:05+00:00`), which SQLite's date functions accept.
+/// `{"$json": <any value>}` is bound as its compact JSON text, for JSON1
+/// columns and functions such as json_extract.
+///
+/// `{"$int": ...}`, `{"$real": ...}` and `{"$text": ...}` take a number or a
+/// string and bind it as INTEGER, REAL or TEXT whatever its JSON type, so
+/// `{"$int": "42"}` matches an integer id where `"42"` would not.
+///
+/// Every tag but `$json` takes null for a NULL, as a bare null would bind;
+/// `{"$json": null}` is the JSON text `null`.
+fn bind_tagged<'q>(
+    query: SqliteQuery<'q>,
+    map: serde_json::Map<String, serde_json::Value>,
+) -> Result<SqliteQuery<'q>, String> {
+    let mut entries = map.into_iter();
+    let (tag, value) = match (entries.next(), entries.next()) {
+        (Some(entry), None) => entry,
+        _ => {
+            return Err(
+                "unsupported parameter type object (wrap it as {\"$json\": ...})".to_string(),
+            )
+        }
+    };
+
+    match (tag.as_str(), value) {
+        ("$blob", serde_json::Value::Null) => Ok(query.bind(None::<Vec<u8>>)),
+        ("$datetime", serde_json::Value::Null) => {
+            Ok(query.bind(None::<chrono::DateTime<chrono::Utc>>))
+        }
+        ("$int", serde_json::Value::Null) => Ok(query.bind(None::<i64>)),
+        ("$real", serde_json::Value::Null) => Ok(query.bind(None::<f64>)),
+        ("$text", serde_json::Value::Null) => Ok(query.bind(None::<String>)),
+        ("$blob", serde_json::Value::String(encoded)) => {
+            let bytes = base64::engine::general_purpose::STANDARD
+                .decode(encoded)
+                .map_err(|_| "invalid base64 blob".to_string())?;
+            Ok(query.bind(bytes))
+        }
+        ("$blob", _) => Err("invalid base64 blob".to_string()),
+        ("$datetime", serde_json::Value::String(text)) => {
+            let datetime = chrono::DateTime::parse_from_rfc3339(&text)
+                .map_err(|e| format!("invalid RFC 3339 datetime {:?}: {}", text, e))?;
+            Ok(query.bind(datetime.with_timezone(&chrono::Utc)))
+        }
+        ("$datetime", _) => Err("invalid RFC 3339 datetime: expected a string".to_string()),
+        ("$json", value) => Ok(query.bind(value.to_string())),
+        ("$int", value) => {
+            let int = match &value {
+                serde_json::Value::Number(n) => n.as_i64(),
+                serde_json::Value::String(text) => text.trim().parse::<i64>().ok(),
+                _ => None,
+            };
+            int.map(|int| query.bind(int))
+                .ok_or_else(|| format!("invalid $int {}: expected a 64-bit integer", value))
+        }
+        ("$real", value) => {
+            let real = match &value {
+                serde_json::Value::Number(n) => n.as_f64(),
+                serde_json::Value::String(text) => text.trim().parse::<f64>().ok(),
+                _ => None,
             };
+            // SQLite would store NaN as NULL
+            real.filter(|real| !real.is_nan())
+                .map(|real| query.bind(real))
+                .ok_or_else(|| format!("invalid $real {}: expected a number", value))
+        }
+        ("$text", serde_json::Value::String(text)) => Ok(query.bind(text)),
+        ("$text", serde_json::Value::Number(n)) => Ok(query.bind(n.to_string())),
+        ("$text", value) => Err(format!(
+            "invalid $text {}: expected a string or number",
+            value
+        )),
+        _ => Err(format!(
+            "unsupported parameter tag {:?}; expected $blob, $datetime, $json, $int, $real or $text",
+            tag
+        )),
+    }
+}
+
+/// Convert a single SQLite value to the nearest JSON type
+///
+/// BLOBs are returned as base64 strings since JSON has no binary type.
+fn value_to_json(value: SqliteValueRef<'_>) -> Result<serde_json::Value, DbError> {
+    if value.is_null() {
+        return Ok(serde_json::Value::Null);
+    }
+
+    let decode_error = |e| DbError::execution(format!("Failed to decode column value: {}", e));
+
+    let type_name = value.type_info().name().to_string();
+    let json = match type_name.as_str() {
+        "INTEGER" => serde_json::Value::from(
+            <i64 as sqlx::Decode<sqlx::Sqlite>>::decode(value).map_err(decode_error)?,
+        ),
+        "REAL" => serde_json::Value::from(
+            <f64 as sqlx::Decode<sqlx::Sqlite>>::decode(value).map_err(decode_error)?,
+        ),
+        "BLOB" => {
+            let bytes =
+                <&[u8] as sqlx::Decode<sqlx::Sqlite>>::decode(value).map_err(decode_error)?;
+            serde_json::Value::String(base64::engine::general_purpose::STANDARD.encode(bytes))
         }
+        _ => serde_json::Value::String(
+            <String as sqlx::Decode<sqlx::Sqlite>>::decode(value).map_err(decode_error)?,
+        ),
+    };
+
+    Ok(json)
+}
+
+/// Convert a row to a JSON array of its column values, in column order
+fn row_to_json(row: &SqliteRow) -> Result<serde_json::Value, DbError> {
+    let mut values = Vec::with_capacity(row.len());
+    for i in 0..row.len() {
+        let value = row
+            .try_get_raw(i)
+            .map_err(|e| DbError::execute(&format!("Failed to read column {}", i), e))?;
+        values.push(value_to_json(value)?);
+    }
+    Ok(serde_json::Value::Array(values))
+}
 
-        // Execute the query
-        query.execute(&mut *tx).await.map_err(|e| {
-            format!("Database operation failed: {}", e)
+/// Convert a row to a JSON object keyed by column name
+///
+/// NULL columns are included as JSON null so every row has the same keys.
+fn row_to_map(row: &SqliteRow) -> Result<serde_json::Map<String, serde_json::Value>, DbError> {
+    let mut map = serde_json::Map::with_capacity(row.len());
+    for (i, column) in row.columns().iter().enumerate() {
+        let value = row.try_get_raw(i).map_err(|e| {
+            DbError::execute(&format!("Failed to read column {}", column.name()), e)
         })?;
+        map.insert(column.name().to_string(), value_to_json(value)?);
+    }
+    Ok(map)
+}
+
+/// Open the pool for a database URL with custom settings
+///
+/// Must be called before anything else uses the URL; once a pool exists its
+/// settings are fixed, so configuring an open database is an error.
+#[tauri::command]
+pub async fn configure_pool(
+    db_url: String,
+    options: PoolOptions,
+    state: State<'_, DbState>,
+) -> Result<(), DbError> {
+    let key = pool_key(&state, &db_url)?;
+    let already_open = {
+        let connections_guard = state.connections.lock().map_err(handle_poison_error)?;
+        connections_guard.contains_key(&key)
+    };
+    if already_open {
+        return Err(DbError::Invalid(
+            "Database is already open; close it before configuring the pool".to_string(),
+        ));
     }
 
-    // Commit transaction
-    tx.commit().await.map_err(|e| format!("Failed to commit transaction: {}", e))?;
+    let attachments = attach::pool_attachments(&state, &key)?;
+    let setup = setup::setup_statements(&state, &key)?;
+    let new_pool = create_pool(&db_url, &options, attachments, setup).await?;
 
-    Ok(TransactionResult {
-        success: true,
-        error: None,
-    })
+    let raced = {
+        let mut connections_guard = state.connections.lock().map_err(handle_poison_error)?;
+        match connections_guard.entry(key) {
+            Entry::Occupied(_) => true,
+            Entry::Vacant(entry) => {
+                entry.insert(new_pool.clone());
+                false
+            }
+        }
+    };
+
+    if raced {
+        // Another call opened the database while we were connecting
+        new_pool.close().await;
+        return Err(DbError::Invalid(
+            "Database is already open; close it before configuring the pool".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Use the pool tauri-plugin-sql opened for a database instead of a second one
+///
+/// Two pools on one file contend for its write lock, so code that also uses
+/// the plugin can register the plugin's pool under `db_url` and every command
+/// here will run on it. `plugin_db` is the string the database was loaded
+/// with in the plugin, if it differs from `db_url`.
+///
+/// The plugin opened the pool with its own settings, so configure_pool,
+/// custom functions, setup statements and attachments don't apply to it.
+/// Like configure_pool this must be called before the URL is used here.
+/// The pool is shared: close_connection closes it for the plugin as well.
+#[tauri::command]
+pub async fn share_plugin_pool(
+    db_url: String,
+    plugin_db: Option<String>,
+    app: AppHandle,
+    state: State<'_, DbState>,
+) -> Result<(), DbError> {
+    let plugin_db = plugin_db.unwrap_or_else(|| db_url.clone());
+    let instances = app
+        .try_state::<tauri_plugin_sql::DbInstances>()
+        .ok_or_else(|| DbError::Invalid("tauri-plugin-sql is not registered".to_string()))?;
+    let pool = match instances.inner().0.read().await.get(&plugin_db) {
+        Some(tauri_plugin_sql::DbPool::Sqlite(pool)) => pool.clone(),
+        None => {
+            return Err(DbError::Invalid(format!(
+                "Database is not loaded in tauri-plugin-sql: {}",
+                plugin_db
+            )))
+        }
+    };
+
+    let key = pool_key(&state, &db_url)?;
+    let mut shared_pools = state.shared_pools.lock().map_err(handle_poison_error)?;
+    let mut connections_guard = state.connections.lock().map_err(handle_poison_error)?;
+    match connections_guard.entry(key) {
+        Entry::Occupied(_) => Err(DbError::Invalid(
+            "Database is already open; close it before sharing the plugin's pool".to_string(),
+        )),
+        Entry::Vacant(entry) => {
+            log::info!(
+                "Using the tauri-plugin-sql pool for {} as {}",
+                plugin_db,
+                entry.key()
+            );
+            shared_pools.insert(entry.key().clone());
+            entry.insert(pool);
+            Ok(())
+        }
+    }
+}
+
+/// Execute multiple SQL statements in a transaction
+///
+/// If the database is busy or locked the whole transaction is retried with
+/// exponential backoff before giving up.
+///
+/// A failed transaction is reported in the result, with `completed_steps`
+/// counting the steps that ran before it failed, timed out or failed to
+/// commit.
+///
+/// Progress is emitted as TransactionProgress every 100 steps and after the
+/// last one, when `progressEventName` is set or the transaction has at least
+/// 100 steps. A retry restarts the count from zero.
+///
+/// Passing a `transaction_id` lets cancel_transaction stop the transaction
+/// between steps; it is then rolled back and reported as cancelled.
+///
+/// After a commit, `db-changed` is emitted with the tables the steps wrote to.
+///
+/// If the transaction fails because the database file became unusable (an
+/// I/O error, or the file was moved) or its pool was closed, the pool is
+/// replaced, so the next call reconnects instead of failing the same way.
+///
+/// With `timing` set each step's result includes how long it took, so slow
+/// steps in a large batch can be found.
+///
+/// With `dryRun` set the steps run and report their results as usual, but
+/// the transaction is rolled back at the end, so nothing is persisted and
+/// no `db-changed` is emitted.
+///
+/// With `parallelReads` set, steps that all read run at the same time on
+/// separate pooled connections instead of one after another, which suits
+/// refreshing several independent views at once.
+///
+/// A transaction that only reads goes to the database's read replica when
+/// set_read_replica has named one.
+#[tauri::command]
+pub async fn execute_transaction(
+    db_url: String,
+    steps: Vec<TransactionStep>,
+    options: Option<TransactionOptions>,
+    transaction_id: Option<String>,
+    app: AppHandle,
+    state: State<'_, DbState>,
+) -> Result<TransactionResult, DbError> {
+    let mut options = options.unwrap_or_default();
+    let registration = transaction_id
+        .map(|id| CancelRegistration::new(&state, id))
+        .transpose()?;
+    let cancel = registration.as_ref().map(|r| r.flag.as_ref());
+    let pool = get_or_create_pool(&state, &db_url).await?;
+
+    let mut resolved = Vec::with_capacity(steps.len());
+    for (index, step) in steps.into_iter().enumerate() {
+        // SQLite would silently ignore everything after the first statement
+        if sql::split_statements(&step.sql).len() > 1 {
+            let error = DbError::Unsupported(format!(
+                "step {}: contains more than one statement; use execute_batch for scripts",
+                index
+            ));
+            return Ok(TransactionResult::failed(Some(index), 0, error));
+        }
+
+        if options.parallel_reads && (step.savepoint || !is_read_step(&step.sql)) {
+            let error = DbError::Invalid(format!(
+                "step {}: parallelReads only allows steps that read, without savepoints",
+                index
+            ));
+            return Ok(TransactionResult::failed(Some(index), 0, error));
+        }
+
+        match step.resolve() {
+            Ok(step) => resolved.push(step),
+            Err(e) => return Ok(TransactionResult::failed(Some(index), 0, e.in_step(index))),
+        }
+    }
+
+    let progress = match options.progress_event_name.take() {
+        Some(event) => Some(ProgressReporter {
+            app: app.clone(),
+            event,
+        }),
+        None if resolved.len() >= PROGRESS_INTERVAL => Some(ProgressReporter {
+            app: app.clone(),
+            event: DEFAULT_PROGRESS_EVENT.to_string(),
+        }),
+        None => None,
+    };
+
+    let completed = AtomicUsize::new(0);
+    let attempt_options = AttemptOptions {
+        mode: options.tx_mode,
+        dry_run: options.dry_run,
+        timing: options.timing,
+        progress: progress.as_ref(),
+        cancel,
+        query_log: state.query_log.recorder(&db_url),
+        completed: &completed,
+    };
+
+    let replica = if reads_only(&resolved, &options) {
+        replica::replica_pool(&state, &db_url).await?
+    } else {
+        None
+    };
+    let outcome = match &replica {
+        Some((replica_url, replica_pool)) => {
+            match run_attempts(replica_pool, &resolved, &options, &attempt_options).await {
+                Err(AttemptError::Failed(error)) if is_broken_pool(replica_pool, &error) => {
+                    log::warn!(
+                        "Read replica {} failed, reading from the primary: {}",
+                        replica_url,
+                        error
+                    );
+                    reopen_pool(&state, replica_url, replica_pool)?;
+                    run_attempts(&pool, &resolved, &options, &attempt_options).await
+                }
+                outcome => outcome,
+            }
+        }
+        None => run_attempts(&pool, &resolved, &options, &attempt_options).await,
+    };
+
+    match outcome {
+        Ok(result) if options.dry_run => Ok(result),
+        Ok(result) => {
+            // A savepoint step that failed was rolled back
+            let committed = resolved
+                .iter()
+                .zip(&result.steps)
+                .filter(|(_, step)| step.success)
+                .map(|(step, _)| step.sql.as_str());
+            notify_db_changed(&app, &db_url, committed);
+            Ok(result)
+        }
+        Err(AttemptError::Cancelled { completed_steps }) => Ok(TransactionResult::cancelled(
+            completed_steps,
+            "Transaction cancelled".to_string(),
+        )),
+        Err(AttemptError::Failed(error)) => {
+            // Otherwise every later call would fail on the same connections
+            if is_broken_pool(&pool, &error) {
+                reopen_pool(&state, &db_url, &pool)?;
+            }
+            // Every step before the failed one ran; a timeout or a failed
+            // commit has no step, but the attempt counted what it ran
+            match error.step() {
+                Some(step) => Ok(TransactionResult::failed(Some(step), step, error)),
+                None => Ok(TransactionResult::failed(
+                    None,
+                    completed.load(Ordering::SeqCst),
+                    error,
+                )),
+            }
+        }
+    }
+}
+
+/// Whether a transaction only reads, so it can run on a read replica
+fn reads_only(steps: &[ResolvedStep], options: &TransactionOptions) -> bool {
+    matches!(options.tx_mode, TransactionMode::Deferred)
+        && !steps.is_empty()
+        && steps
+            .iter()
+            .all(|step| !step.savepoint && is_read_step(&step.sql))
+}
+
+/// Run the steps on `pool` as the options ask, giving up after their timeout
+async fn run_attempts(
+    pool: &sqlx::SqlitePool,
+    steps: &[ResolvedStep],
+    options: &TransactionOptions,
+    attempt_options: &AttemptOptions<'_>,
+) -> Result<TransactionResult, AttemptError> {
+    let attempts = async {
+        if options.parallel_reads {
+            run_parallel_reads(pool, steps, attempt_options).await
+        } else {
+            let busy_retries = options.busy_retries.unwrap_or(DEFAULT_BUSY_RETRIES);
+            // A cancelled attempt passes through untouched, since only busy failures are retried
+            run_with_retries(busy_retries, move || async move {
+                match run_transaction(pool, steps, attempt_options).await {
+                    Err(AttemptError::Failed(error)) => Err(error),
+                    outcome => Ok(outcome),
+                }
+            })
+            .await
+            .unwrap_or_else(|error| Err(error.into()))
+        }
+    };
+
+    // Dropping the attempt on timeout drops its transaction, which rolls back
+    match options.timeout_ms {
+        Some(timeout_ms) => tokio::time::timeout(Duration::from_millis(timeout_ms), attempts)
+            .await
+            .unwrap_or(Err(AttemptError::Failed(DbError::Timeout { timeout_ms }))),
+        None => attempts.await,
+    }
+}
+
+/// Run an attempt, retrying with exponential backoff while the database is busy
+async fn run_with_retries<T, F, Fut>(busy_retries: u32, mut run: F) -> Result<T, DbError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, DbError>>,
+{
+    let mut attempt = 0;
+    loop {
+        let error = match run().await {
+            Ok(result) => return Ok(result),
+            Err(error) => error,
+        };
+        if !error.is_busy() {
+            return Err(error);
+        }
+
+        if attempt >= busy_retries {
+            log::warn!(
+                "Database busy, gave up after {} retries: {}",
+                busy_retries,
+                error
+            );
+            return Err(error.retries_exhausted(busy_retries));
+        }
+
+        let backoff = BUSY_RETRY_BASE_DELAY
+            .saturating_mul(1 << attempt.min(16))
+            .min(BUSY_RETRY_MAX_DELAY);
+        log::warn!(
+            "Database busy (attempt {} of {}), retrying in {:?}: {}",
+            attempt + 1,
+            busy_retries + 1,
+            backoff,
+            error
+        );
+        tokio::time::sleep(backoff).await;
+        attempt += 1;
+    }
+}
+
+/// What a single step produced
+struct StepOutput {
+    columns: Vec<String>,
+    column_types: Vec<String>,
+    rows: Vec<serde_json::Value>,
+    result: StepResult,
+}
+
+/// Run resolved steps in a single transaction attempt
+async fn run_transaction(
+    pool: &sqlx::SqlitePool,
+    steps: &[ResolvedStep],
+    options: &AttemptOptions<'_>,
+) -> Result<TransactionResult, AttemptError> {
+    let check_cancelled = |completed_steps| match options.cancel {
+        Some(flag) if flag.load(Ordering::Relaxed) => {
+            Err(AttemptError::Cancelled { completed_steps })
+        }
+        _ => Ok(()),
+    };
+
+    options.completed.store(0, Ordering::SeqCst);
+
+    // Begin transaction
+    let mut tx = pool
+        .begin_with(options.mode.begin_sql())
+        .await
+        .map_err(|e| DbError::begin("Failed to begin transaction", e))?;
+
+    let mut results = Vec::with_capacity(steps.len());
+    let mut columns = Vec::with_capacity(steps.len());
+    let mut column_types = Vec::with_capacity(steps.len());
+    let mut step_results = Vec::with_capacity(steps.len());
+
+    // Execute all steps
+    for (index, step) in steps.iter().enumerate() {
+        // Returning drops the transaction, which rolls it back
+        check_cancelled(index)?;
+
+        let started = (options.timing || options.query_log.is_some()).then(Instant::now);
+        let output = if step.savepoint {
+            run_savepoint_step(&mut tx, index, step).await
+        } else {
+            run_step(&mut tx, index, step).await
+        };
+        let output = record_step(options, step, started, output)?;
+
+        results.push(output.rows);
+        columns.push(output.columns);
+        column_types.push(output.column_types);
+        step_results.push(output.result);
+
+        options.completed.store(index + 1, Ordering::SeqCst);
+        if let Some(progress) = options.progress {
+            progress.step_completed(index + 1, steps.len());
+        }
+    }
+
+    check_cancelled(steps.len())?;
+
+    if options.dry_run {
+        tx.rollback()
+            .await
+            .map_err(|e| DbError::execute("Failed to roll back dry run", e))?;
+    } else {
+        tx.commit()
+            .await
+            .map_err(|e| DbError::commit("Failed to commit transaction", e))?;
+    }
+
+    Ok(TransactionResult {
+        success: true,
+        error: None,
+        error_detail: None,
+        results,
+        columns,
+        column_types,
+        steps: step_results,
+        failed_step: None,
+        completed_steps: steps.len(),
+        cancelled: false,
+    })
+}
+
+/// Log a step that ran since `started` and, with timing on, add its duration
+fn record_step(
+    options: &AttemptOptions<'_>,
+    step: &ResolvedStep,
+    started: Option<Instant>,
+    output: Result<StepOutput, DbError>,
+) -> Result<StepOutput, DbError> {
+    let elapsed = started.map(|started| started.elapsed());
+    if let (Some(recorder), Some(elapsed)) = (&options.query_log, elapsed) {
+        recorder.record(
+            &step.sql,
+            &step.params,
+            elapsed,
+            output.as_ref().map(|output| &output.result),
+        );
+    }
+    let mut output = output?;
+    if options.timing {
+        output.result.duration_micros =
+            elapsed.map(|elapsed| u64::try_from(elapsed.as_micros()).unwrap_or(u64::MAX));
+    }
+    Ok(output)
+}
+
+/// Whether a step only reads, so it can run outside a transaction
+fn is_read_step(sql: &str) -> bool {
+    matches!(
+        sql::statement_keyword(sql).as_deref(),
+        Some("SELECT" | "VALUES")
+    )
+}
+
+/// Run read steps concurrently, each on a connection of its own
+///
+/// Every step runs even if an earlier one fails, and the first failure in
+/// step order is reported, so `completed_steps` still counts the steps
+/// before it.
+async fn run_parallel_reads(
+    pool: &sqlx::SqlitePool,
+    steps: &[ResolvedStep],
+    options: &AttemptOptions<'_>,
+) -> Result<TransactionResult, AttemptError> {
+    let completed = options.completed;
+    completed.store(0, Ordering::SeqCst);
+    let reads = steps.iter().enumerate().map(|(index, step)| async move {
+        let mut conn = pool
+            .acquire()
+            .await
+            .map_err(|e| DbError::connect("Failed to acquire connection", e))?;
+        if options
+            .cancel
+            .is_some_and(|flag| flag.load(Ordering::Relaxed))
+        {
+            return Err(AttemptError::Cancelled {
+                completed_steps: completed.load(Ordering::SeqCst),
+            });
+        }
+
+        let started = (options.timing || options.query_log.is_some()).then(Instant::now);
+        let output = run_step(&mut conn, index, step).await;
+        let output = record_step(options, step, started, output)?;
+
+        let done = completed.fetch_add(1, Ordering::SeqCst) + 1;
+        if let Some(progress) = options.progress {
+            progress.step_completed(done, steps.len());
+        }
+        Ok(output)
+    });
+    let outputs = futures_util::future::join_all(reads).await;
+
+    let mut results = Vec::with_capacity(steps.len());
+    let mut columns = Vec::with_capacity(steps.len());
+    let mut column_types = Vec::with_capacity(steps.len());
+    let mut step_results = Vec::with_capacity(steps.len());
+    for output in outputs {
+        let output = output?;
+        results.push(output.rows);
+        columns.push(output.columns);
+        column_types.push(output.column_types);
+        step_results.push(output.result);
+    }
+
+    Ok(TransactionResult {
+        success: true,
+        error: None,
+        error_detail: None,
+        results,
+        columns,
+        column_types,
+        steps: step_results,
+        failed_step: None,
+        completed_steps: steps.len(),
+        cancelled: false,
+    })
+}
+
+/// Run a step inside `SAVEPOINT sp_<index>`, rolling back just this step on failure
+///
+/// Busy errors still fail the attempt so the whole transaction can be retried.
+async fn run_savepoint_step(
+    conn: &mut sqlx::SqliteConnection,
+    index: usize,
+    step: &ResolvedStep,
+) -> Result<StepOutput, DbError> {
+    let savepoint = format!("sp_{}", index);
+    let savepoint_error = |e| DbError::execute("savepoint error", e).in_step(index);
+
+    conn.execute(format!("SAVEPOINT {}", savepoint).as_str())
+        .await
+        .map_err(savepoint_error)?;
+
+    match run_step(conn, index, step).await {
+        Ok(output) => {
+            conn.execute(format!("RELEASE {}", savepoint).as_str())
+                .await
+                .map_err(savepoint_error)?;
+            Ok(output)
+        }
+        Err(error) if error.is_busy() => Err(error),
+        Err(error) => {
+            conn.execute(format!("ROLLBACK TO {}", savepoint).as_str())
+                .await
+                .map_err(savepoint_error)?;
+            conn.execute(format!("RELEASE {}", savepoint).as_str())
+                .await
+                .map_err(savepoint_error)?;
+            Ok(StepOutput {
+                columns: Vec::new(),
+                column_types: Vec::new(),
+                rows: Vec::new(),
+                result: StepResult {
+                    success: false,
+                    error: Some(error.to_string()),
+                    error_detail: Some(error),
+                    rows_affected: 0,
+                    last_insert_rowid: None,
+                    duration_micros: None,
+                },
+            })
+        }
+    }
+}
+
+/// Prepare, bind and execute one step, collecting any rows it returns
+async fn run_step(
+    conn: &mut sqlx::SqliteConnection,
+    index: usize,
+    step: &ResolvedStep,
+) -> Result<StepOutput, DbError> {
+    // Prepare first so column names are known even when no rows come back
+    let statement = (&mut *conn)
+        .prepare(step.sql.as_str())
+        .await
+        .map_err(|e| DbError::execute("execution error", e).in_step(index))?;
+    let columns: Vec<String> = statement
+        .columns()
+        .iter()
+        .map(|column| column.name().to_string())
+        .collect();
+    let column_types: Vec<String> = statement
+        .columns()
+        .iter()
+        .map(|column| column.type_info().name().to_string())
+        .collect();
+
+    // SQLite's change counter is only updated by writes, so reads would
+    // otherwise report the count left behind by an earlier statement
+    let keyword = sql::statement_keyword(&step.sql);
+    let writes = matches!(
+        keyword.as_deref(),
+        Some("INSERT" | "REPLACE" | "UPDATE" | "DELETE")
+    );
+    let inserts = matches!(keyword.as_deref(), Some("INSERT" | "REPLACE"));
+    let query =
+        bind_params(statement.query(), step.params.clone()).map_err(|e| e.in_step(index))?;
+
+    // Execute the query, keeping any rows it produces
+    let mut rows = Vec::new();
+    let mut rows_affected = 0;
+    let mut last_insert_rowid = None;
+    let mut stream = (&mut *conn).fetch_many(query);
+    while let Some(item) = stream
+        .try_next()
+        .await
+        .map_err(|e| DbError::execute("execution error", e).in_step(index))?
+    {
+        match item {
+            Either::Left(result) if writes => {
+                rows_affected += result.rows_affected();
+                if inserts && result.rows_affected() > 0 {
+                    last_insert_rowid = Some(result.last_insert_rowid());
+                }
+            }
+            Either::Left(_) => {}
+            Either::Right(row) => {
+                let row = row_to_json(&row).map_err(|e| e.in_step(index))?;
+                rows.push(row);
+            }
+        }
+    }
+
+    if let Some(expected) = step.expect_rows_affected {
+        if rows_affected != expected {
+            return Err(DbError::Conflict {
+                step: Some(index),
+                expected,
+                actual: rows_affected,
+            });
+        }
+    }
+
+    Ok(StepOutput {
+        columns,
+        column_types,
+        rows,
+        result: StepResult {
+            success: true,
+            error: None,
+            error_detail: None,
+            rows_affected,
+            last_insert_rowid,
+            duration_micros: None,
+        },
+    })
+}
+
+/// Ask a running transaction started with `transaction_id` to stop
+///
+/// Returns whether a running transaction was signalled. Cancelling one that
+/// has already finished, or an unknown id, does nothing.
+#[tauri::command]
+pub fn cancel_transaction(
+    transaction_id: String,
+    state: State<'_, DbState>,
+) -> Result<bool, DbError> {
+    let cancellations = state.cancellations.lock().map_err(handle_poison_error)?;
+    let Some(flag) = cancellations.get(&transaction_id) else {
+        return Ok(false);
+    };
+
+    flag.store(true, Ordering::Relaxed);
+    Ok(true)
+}
+
+/// Run a single read query and return its rows keyed by column name
+#[tauri::command]
+pub async fn query_rows(
+    db_url: String,
+    sql: String,
+    params: Vec<serde_json::Value>,
+    state: State<'_, DbState>,
+) -> Result<Vec<serde_json::Map<String, serde_json::Value>>, DbError> {
+    let pool = get_or_create_pool(&state, &db_url).await?;
+
+    let query = bind_params(sqlx::query(&sql), params)?;
+    let rows = query
+        .fetch_all(&pool)
+        .await
+        .map_err(|e| DbError::execute("Database operation failed", e))?;
+
+    rows.iter().map(row_to_map).collect()
+}
+
+/// Rows keyed by column name, as query_rows returns them
+pub type Rows = Vec<serde_json::Map<String, serde_json::Value>>;
+
+/// One read query for batch_across_dbs
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DbRequest {
+    pub db_url: String,
+    pub sql: String,
+    #[serde(default)]
+    pub params: Vec<serde_json::Value>,
+}
+
+/// Run read queries against several databases concurrently in one call
+///
+/// Each request runs on the pool for its own database URL, like query_rows.
+/// Results are returned in request order, each `{ "Ok": rows }` or
+/// `{ "Err": error }`, so one failing query doesn't hide the others.
+#[tauri::command]
+pub async fn batch_across_dbs(
+    requests: Vec<DbRequest>,
+    state: State<'_, DbState>,
+) -> Result<Vec<Result<Rows, DbError>>, DbError> {
+    let queries = requests
+        .into_iter()
+        .map(|request| query_rows(request.db_url, request.sql, request.params, state.clone()));
+
+    Ok(futures_util::future::join_all(queries).await)
+}
+
+/// Run a read query on a read-only pool for the database URL
+///
+/// Like query_rows, but any attempt to write fails with SQLite's readonly
+/// error, so reporting views cannot modify data by accident.
+#[tauri::command]
+pub async fn query_rows_readonly(
+    db_url: String,
+    sql: String,
+    params: Vec<serde_json::Value>,
+    state: State<'_, DbState>,
+) -> Result<Vec<serde_json::Map<String, serde_json::Value>>, DbError> {
+    let pool = get_or_create_read_only_pool(&state, &db_url).await?;
+
+    let query = bind_params(sqlx::query(&sql), params)?;
+    let rows = query
+        .fetch_all(&pool)
+        .await
+        .map_err(|e| DbError::execute("Database operation failed", e))?;
+
+    rows.iter().map(row_to_map).collect()
+}
+
+/// Show how SQLite would run a query, without running it
+///
+/// Parameters are bound like query_rows so the plan reflects real values.
+/// Look for `SCAN` details without `USING INDEX` to spot full-table scans.
+#[tauri::command]
+pub async fn explain_query_plan(
+    db_url: String,
+    sql: String,
+    params: Vec<serde_json::Value>,
+    state: State<'_, DbState>,
+) -> Result<Vec<QueryPlanStep>, DbError> {
+    let pool = get_or_create_pool(&state, &db_url).await?;
+
+    let explain_sql = format!("EXPLAIN QUERY PLAN {}", sql);
+    let query = bind_params(sqlx::query(&explain_sql), params)?;
+    let rows = query
+        .fetch_all(&pool)
+        .await
+        .map_err(|e| DbError::execute("Failed to explain query", e))?;
+
+    rows.iter()
+        .map(|row| {
+            Ok(QueryPlanStep {
+                id: row.try_get("id")?,
+                parent: row.try_get("parent")?,
+                detail: row.try_get("detail")?,
+            })
+        })
+        .collect::<Result<_, sqlx::Error>>()
+        .map_err(|e| DbError::execute("Failed to read query plan", e))
+}
+
+/// Show a database file in Finder, Explorer or the system file manager
+///
+/// Opens the folder containing the file with the file selected. Fails for
+/// in-memory databases and URLs that don't name a file on disk.
+#[cfg(desktop)]
+#[tauri::command]
+pub fn reveal_database(db_url: String) -> Result<(), DbError> {
+    let path = db_file_path(&db_url)?;
+    tauri_plugin_opener::reveal_item_in_dir(&path)
+        .map_err(|e| DbError::Invalid(format!("Failed to reveal {}: {}", path.display(), e)))
+}
+
+/// Close the pool for a database URL and forget it
+///
+/// Open cursors on the database are stopped first, since closing a pool
+/// waits for the connections they hold.
+#[tauri::command]
+pub async fn close_connection(
+    db_url: String,
+    state: State<'_, DbState>,
+    cursors: State<'_, cursor::CursorState>,
+) -> Result<(), DbError> {
+    cursors.close_database(&pool_key(&state, &db_url)?).await?;
+    let pools = forget_pools(&state, &db_url)?;
+    if pools.is_empty() {
+        return Err(DbError::Invalid(format!(
+            "Database is not open: {}",
+            db_url
+        )));
+    }
+
+    for pool in pools {
+        pool.close().await;
+    }
+
+    Ok(())
+}
+
+/// Close every open pool, stopping any open cursors first
+#[tauri::command]
+pub async fn close_all_connections(
+    state: State<'_, DbState>,
+    cursors: State<'_, cursor::CursorState>,
+) -> Result<(), DbError> {
+    cursors.close_all().await?;
+    close_pools(&state).await
+}
+
+/// Close and forget every pool
+async fn close_pools(state: &DbState) -> Result<(), DbError> {
+    let mut pools: Vec<(String, sqlx::SqlitePool)> = {
+        state
+            .attachments
+            .lock()
+            .map_err(handle_poison_error)?
+            .clear();
+        state
+            .shared_pools
+            .lock()
+            .map_err(handle_poison_error)?
+            .clear();
+        let mut connections_guard = state.connections.lock().map_err(handle_poison_error)?;
+        connections_guard.drain().collect()
+    };
+
+    // The last connection to a WAL database checkpoints it as it closes,
+    // which a read-only connection can't, so those close first
+    pools.sort_by_key(|(key, _)| !key.ends_with(READ_ONLY_SUFFIX));
+    for (_, pool) in pools {
+        pool.close().await;
+    }
+
+    Ok(())
+}
+
+/// Longest that closing pools may hold up the app exiting
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Close every pool as the app exits
+///
+/// Dropped pools don't close their connections cleanly, which leaves a WAL
+/// database's -wal and -shm files behind until it is next opened. Open
+/// cursors are stopped first, since closing a pool waits for the connections
+/// they hold. If closing takes longer than SHUTDOWN_TIMEOUT the app exits
+/// anyway.
+pub fn close_all_on_exit(app: &AppHandle) {
+    let state = app.state::<DbState>();
+    let cursors = app.state::<cursor::CursorState>();
+    tauri::async_runtime::block_on(async {
+        let close = async {
+            cursors.close_all().await?;
+            close_pools(&state).await
+        };
+        match tokio::time::timeout(SHUTDOWN_TIMEOUT, close).await {
+            Ok(Ok(())) => log::info!("Closed all database connections"),
+            Ok(Err(e)) => log::warn!("Failed to close database connections: {}", e),
+            Err(_) => log::warn!(
+                "Database connections didn't close within {:?}; exiting anyway",
+                SHUTDOWN_TIMEOUT
+            ),
+        }
+    });
+}
+
+/// List open pools with their current connection counts
+#[tauri::command]
+pub fn list_connections(state: State<'_, DbState>) -> Result<Vec<ConnectionInfo>, DbError> {
+    let mut connections: Vec<ConnectionInfo> = {
+        let connections_guard = state.connections.lock().map_err(handle_poison_error)?;
+        connections_guard
+            .iter()
+            .map(|(url, pool)| ConnectionInfo {
+                url: url.clone(),
+                size: pool.size(),
+                idle: pool.num_idle(),
+            })
+            .collect()
+    };
+
+    connections.sort_by(|a, b| a.url.cmp(&b.url));
+
+    Ok(connections)
+}
+
+/// Longest ping_connections waits for one pool to answer
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Run `SELECT 1` on every open pool and report which answered and how fast
+///
+/// A pool whose query fails is replaced as by a connection error in
+/// execute_transaction: the new pool keeps the settings configure_pool gave
+/// the old one, and the old one closes once its connections are returned.
+/// A pool shared with tauri-plugin-sql is only reported, since the plugin
+/// owns it. A pool that is merely busy, with every connection in use until
+/// the ping times out, is reported as not ok but kept.
+#[tauri::command]
+pub async fn ping_connections(state: State<'_, DbState>) -> Result<Vec<PingResult>, DbError> {
+    let pools: Vec<(String, sqlx::SqlitePool)> = {
+        let connections_guard = state.connections.lock().map_err(handle_poison_error)?;
+        connections_guard
+            .iter()
+            .map(|(url, pool)| (url.clone(), pool.clone()))
+            .collect()
+    };
+
+    let pings = pools.into_iter().map(|(url, pool)| async move {
+        let started = Instant::now();
+        let ping = sqlx::query_scalar::<_, i64>("SELECT 1").fetch_one(&pool);
+        let (error, failed) = match tokio::time::timeout(PING_TIMEOUT, ping).await {
+            Ok(Ok(_)) => (None, false),
+            Ok(Err(sqlx::Error::PoolTimedOut)) => (
+                Some("No connection was free to ping with".to_string()),
+                false,
+            ),
+            Ok(Err(e)) => (Some(DbError::execute("Ping failed", e).to_string()), true),
+            Err(_) => (
+                Some(format!("No response within {:?}", PING_TIMEOUT)),
+                false,
+            ),
+        };
+        let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+        (url, pool, error, failed, latency_ms)
+    });
+    let pings = futures_util::future::join_all(pings).await;
+
+    let mut results = Vec::with_capacity(pings.len());
+    for (url, pool, error, failed, latency_ms) in pings {
+        let mut evicted = false;
+        if failed {
+            log::warn!(
+                "Ping failed on pool {}: {}",
+                url,
+                error.as_deref().unwrap_or_default()
+            );
+            evicted = replace_pool(&state, &url, &pool)?;
+        }
+        results.push(PingResult {
+            url,
+            ok: error.is_none(),
+            latency_ms,
+            error,
+            evicted,
+        });
+    }
+    results.sort_by(|a, b| a.url.cmp(&b.url));
+
+    Ok(results)
+}
+
+/// Report connection counts and the configured maximum for an open pool
+#[tauri::command]
+pub fn pool_stats(db_url: String, state: State<'_, DbState>) -> Result<PoolStats, DbError> {
+    let pool = get_open_pool(&state, &db_url)?;
+
+    Ok(PoolStats {
+        size: pool.size(),
+        idle: pool.num_idle(),
+        max_connections: pool.options().get_max_connections(),
+    })
+}
+
+/// Execute a multi-statement script without wrapping it in a transaction
+///
+/// Statements run one at a time on a single connection, so PRAGMAs and VACUUM
+/// that cannot run inside BEGIN/COMMIT work as expected. Statements before a
+/// failure stay applied; use execute_transaction when atomicity is needed.
+///
+/// `db-changed` is emitted with the tables written by the statements that ran.
+#[tauri::command]
+pub async fn execute_batch(
+    db_url: String,
+    sql: String,
+    app: AppHandle,
+    state: State<'_, DbState>,
+) -> Result<BatchResult, DbError> {
+    let pool = get_or_create_pool(&state, &db_url).await?;
+    let mut conn = pool
+        .acquire()
+        .await
+        .map_err(|e| DbError::connect("Failed to acquire connection", e))?;
+
+    let statements = sql::split_statements(&sql);
+    let mut statements_executed = 0;
+    for statement in statements.iter().copied() {
+        if let Err(e) = (&mut *conn).execute(statement).await {
+            notify_db_changed(
+                &app,
+                &db_url,
+                statements[..statements_executed].iter().copied(),
+            );
+            let error = DbError::execute(&format!("statement {}", statements_executed), e);
+            return Ok(BatchResult {
+                success: false,
+                error: Some(error.to_string()),
+                error_detail: Some(error),
+                statements_executed,
+                failed_statement: Some(statement.to_string()),
+            });
+        }
+        statements_executed += 1;
+    }
+    notify_db_changed(&app, &db_url, statements.iter().copied());
+
+    Ok(BatchResult {
+        success: true,
+        error: None,
+        error_detail: None,
+        statements_executed,
+        failed_statement: None,
+    })
+}
+
+/// Check that a PRAGMA name is `name` or `schema.name` made of identifier characters
+fn validate_pragma_name(pragma: &str) -> Result<(), DbError> {
+    let valid = pragma.split('.').count() <= 2
+        && pragma.split('.').all(|part| {
+            part.chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        });
+
+    if valid {
+        Ok(())
+    } else {
+        Err(DbError::Invalid(format!("Invalid PRAGMA name: {}", pragma)))
+    }
+}
+
+/// Render a PRAGMA value as SQL
+///
+/// Keywords (WAL, NORMAL, ON) and integers are passed through, anything else
+/// is quoted as a string literal since PRAGMA values cannot be bound.
+fn pragma_value_sql(value: &str) -> String {
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    let is_integer = !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit());
+    let is_keyword = value
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    if is_integer || is_keyword {
+        value.to_string()
+    } else {
+        quote_literal(value)
+    }
+}
+
+/// Run a single PRAGMA outside of any transaction
+///
+/// Builds `PRAGMA name = value`, or `PRAGMA name` to read the current
+/// setting, and returns whatever rows the PRAGMA produces (for example the
+/// resulting mode from `journal_mode`). Per-connection settings such as
+/// `synchronous` only apply to the pooled connection that ran them; use
+/// configure_pool for settings every connection needs.
+///
+/// # Arguments
+/// * `pragma` - PRAGMA name, optionally schema-qualified (`main.page_size`)
+/// * `value` - Value to set, or None to query
+#[tauri::command]
+pub async fn run_pragma(
+    db_url: String,
+    pragma: String,
+    value: Option<String>,
+    state: State<'_, DbState>,
+) -> Result<Vec<serde_json::Map<String, serde_json::Value>>, DbError> {
+    validate_pragma_name(&pragma)?;
+
+    let sql = match &value {
+        Some(value) => format!("PRAGMA {} = {}", pragma, pragma_value_sql(value)),
+        None => format!("PRAGMA {}", pragma),
+    };
+
+    let pool = get_or_create_pool(&state, &db_url).await?;
+    let rows = sqlx::query(&sql)
+        .fetch_all(&pool)
+        .await
+        .map_err(|e| DbError::Execute {
+            step: None,
+            code: sqlite_code(&e),
+            message: format!("PRAGMA failed: {} | Statement: {}", e, sql),
+        })?;
+
+    rows.iter().map(row_to_map).collect()
+}
+
+/// Change the passphrase of an encrypted database
+///
+/// The database must already be encrypted and its pool opened with the
+/// current passphrase through configure_pool. Other pooled connections still
+/// hold the old key, so the pool is closed afterwards; reopen it with the new
+/// passphrase.
+#[tauri::command]
+pub async fn rekey_database(
+    db_url: String,
+    new_passphrase: String,
+    state: State<'_, DbState>,
+) -> Result<(), DbError> {
+    require_sqlcipher()?;
+    if new_passphrase.is_empty() {
+        return Err(DbError::Invalid(
+            "New passphrase cannot be empty".to_string(),
+        ));
+    }
+
+    let pool = get_open_pool(&state, &db_url)?;
+
+    let sql = format!("PRAGMA rekey = {}", quote_literal(&new_passphrase));
+    pool.execute(sql.as_str())
+        .await
+        .map_err(|e| DbError::execute("Failed to rekey database", e))?;
+
+    drop(pool);
+    for pool in forget_pools(&state, &db_url)? {
+        pool.close().await;
+    }
+
+    log::info!("Rekeyed database: {}", db_url);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::Connection;
+
+    fn step(sql: &str, expect_rows_affected: Option<u64>) -> ResolvedStep {
+        ResolvedStep {
+            sql: sql.to_string(),
+            params: Vec::new(),
+            savepoint: false,
+            expect_rows_affected,
+        }
+    }
+
+    fn db_state() -> DbState {
+        DbState {
+            connections: Default::default(),
+            attachments: Default::default(),
+            setup_statements: Default::default(),
+            cancellations: Default::default(),
+            query_log: query_log::QueryLog::default(),
+            pool_keys: Default::default(),
+            read_replicas: Default::default(),
+            shared_pools: Default::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn expect_rows_affected_counts_commented_and_with_steps() {
+        let mut conn = sqlx::SqliteConnection::connect("sqlite::memory:")
+            .await
+            .unwrap();
+        conn.execute("CREATE TABLE t (x INTEGER)").await.unwrap();
+
+        let insert = step(
+            "-- seed\n/* two rows */ INSERT INTO t VALUES (1), (2)",
+            Some(2),
+        );
+        let output = run_step(&mut conn, 0, &insert).await.unwrap();
+        assert_eq!(output.result.rows_affected, 2);
+        assert_eq!(output.result.last_insert_rowid, Some(2));
+
+        let update = step(
+            "WITH ids AS (SELECT 1 AS x) UPDATE t SET x = 3 WHERE x IN (SELECT x FROM ids)",
+            Some(1),
+        );
+        let output = run_step(&mut conn, 1, &update).await.unwrap();
+        assert_eq!(output.result.rows_affected, 1);
+        assert_eq!(output.result.last_insert_rowid, None);
+
+        let stale = step(
+            "WITH ids AS (SELECT 1 AS x) UPDATE t SET x = 4 WHERE x IN (SELECT x FROM ids)",
+            Some(1),
+        );
+        match run_step(&mut conn, 2, &stale).await {
+            Err(DbError::Conflict {
+                step,
+                expected,
+                actual,
+            }) => assert_eq!((step, expected, actual), (Some(2), 1, 0)),
+            other => panic!("expected a conflict, got {:?}", other.map(|o| o.result)),
+        }
+
+        let read = step("/* all */ SELECT x FROM t", Some(0));
+        let output = run_step(&mut conn, 3, &read).await.unwrap();
+        assert_eq!(output.rows.len(), 2);
+        assert!(is_read_step(&read.sql));
+        assert!(!is_read_step(&update.sql));
+    }
+
+    #[tokio::test]
+    async fn broken_pools_are_replaced() {
+        let path = std::env::temp_dir().join(format!("invariant-reopen-{}.db", std::process::id()));
+        let db_url = format!("sqlite:{}?mode=rw", path.display());
+        let create_file = || async {
+            let create_url = format!("sqlite:{}?mode=rwc", path.display());
+            let mut conn = sqlx::SqliteConnection::connect(&create_url).await.unwrap();
+            conn.execute("CREATE TABLE t (x INTEGER)").await.unwrap();
+            conn.close().await.unwrap();
+        };
+        let state = db_state();
+        let steps = [step("INSERT INTO t VALUES (1)", Some(1))];
+        let options = TransactionOptions::default();
+        let attempt = AttemptOptions {
+            mode: options.tx_mode,
+            dry_run: false,
+            timing: false,
+            progress: None,
+            cancel: None,
+            query_log: None,
+            completed: &AtomicUsize::new(0),
+        };
+        let run = |pool: sqlx::SqlitePool| {
+            let (steps, options, attempt) = (&steps, &options, &attempt);
+            async move {
+                match run_attempts(&pool, steps, options, attempt).await {
+                    Ok(result) => Ok(result.success),
+                    Err(AttemptError::Failed(error)) => Err(error),
+                    Err(AttemptError::Cancelled { .. }) => unreachable!(),
+                }
+            }
+        };
+
+        create_file().await;
+        let pool = get_or_create_pool(&state, &db_url).await.unwrap();
+        assert!(run(pool.clone()).await.unwrap());
+
+        // A closed pool fails every call until it is replaced
+        pool.close().await;
+        let error = run(pool.clone()).await.unwrap_err();
+        assert!(is_broken_pool(&pool, &error));
+        reopen_pool(&state, &db_url, &pool).unwrap();
+        let reopened = get_or_create_pool(&state, &db_url).await.unwrap();
+        assert!(!reopened.is_closed());
+
+        // So does one whose file is gone
+        std::fs::remove_file(&path).unwrap();
+        let error = run(reopened.clone()).await.unwrap_err();
+        assert!(error.is_connection_error(), "{}", error);
+        assert!(is_broken_pool(&reopened, &error));
+        reopen_pool(&state, &db_url, &reopened).unwrap();
+
+        create_file().await;
+        let recreated = get_or_create_pool(&state, &db_url).await.unwrap();
+        assert!(run(recreated.clone()).await.unwrap());
+
+        recreated.close().await;
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+
+    /// Repeated inserts keep their prepared statement with the default
+    /// cache, and keep nothing with `statementCacheCapacity` 0
+    #[tokio::test]
+    async fn statement_cache_capacity_reuses_repeated_inserts() {
+        const INSERTS: i64 = 2_000;
+
+        for capacity in [Some(0), None] {
+            let options = PoolOptions {
+                statement_cache_capacity: capacity,
+                ..PoolOptions::default()
+            };
+            let pool = create_pool(
+                "sqlite::memory:",
+                &options,
+                Arc::default(),
+                Arc::from(Vec::new()),
+            )
+            .await
+            .unwrap();
+            sqlx::query("CREATE TABLE t (x INTEGER, label TEXT)")
+                .execute(&pool)
+                .await
+                .unwrap();
+
+            for i in 0..INSERTS {
+                sqlx::query("INSERT INTO t (x, label) VALUES (?, ?)")
+                    .bind(i)
+                    .bind("row")
+                    .execute(&pool)
+                    .await
+                    .unwrap();
+            }
+
+            let mut conn = pool.acquire().await.unwrap();
+            let count: i64 = sqlx::query_scalar("SELECT count(*) FROM t")
+                .fetch_one(&mut *conn)
+                .await
+                .unwrap();
+            assert_eq!(count, INSERTS);
+            let cached = conn.cached_statements_size();
+            if capacity == Some(0) {
+                assert_eq!(cached, 0);
+            } else {
+                assert!(cached > 0);
+            }
+        }
+    }
 }
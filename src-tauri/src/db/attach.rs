@@ -0,0 +1,211 @@
+//! Databases attached to every connection of a pool
+//!
+//! `ATTACH` only affects the connection it runs on, so attaching through a
+//! single query would leave the rest of the pool without the alias. Instead
+//! each pool keeps its list of attachments: new connections attach all of
+//! them as they open, and connections opened before the list last changed
+//! are closed when next acquired, so the pool replaces them with fresh ones.
+//!
+//! An in-memory database can't be reopened without losing its data, so its
+//! single connection is never replaced: attach and detach run directly on it.
+//! Its connection is opened in memory mode, which a plain path would inherit
+//! and attach an empty in-memory database, so the file is attached by URI.
+
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use sqlx::pool::PoolConnectionMetadata;
+use sqlx::SqliteConnection;
+use tauri::State;
+
+use super::sql::quote_identifier;
+use super::{
+    get_open_pool, get_or_create_pool, handle_poison_error, is_in_memory, pool_key, DbError,
+    DbState,
+};
+
+/// The databases a pool attaches on every connection
+#[derive(Default)]
+pub struct Attachments {
+    inner: Mutex<AttachmentList>,
+}
+
+#[derive(Default)]
+struct AttachmentList {
+    /// (alias, path) pairs in the order they were attached
+    databases: Vec<(String, String)>,
+    /// When `databases` last changed; connections older than this are stale
+    changed_at: Option<Instant>,
+}
+
+impl Attachments {
+    /// Attach every registered database to a newly opened connection
+    pub(super) async fn attach_all(&self, conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+        let databases = match self.inner.lock() {
+            Ok(list) => list.databases.clone(),
+            Err(poisoned) => poisoned.into_inner().databases.clone(),
+        };
+
+        for (alias, path) in databases {
+            sqlx::query(&format!(
+                "ATTACH DATABASE ? AS {}",
+                quote_identifier(&alias)
+            ))
+            .bind(path)
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether an idle connection was opened after the attachments last changed
+    pub(super) fn is_current(&self, meta: &PoolConnectionMetadata) -> bool {
+        let changed_at = match self.inner.lock() {
+            Ok(list) => list.changed_at,
+            Err(poisoned) => poisoned.into_inner().changed_at,
+        };
+
+        match (changed_at, Instant::now().checked_sub(meta.age)) {
+            (Some(changed_at), Some(opened_at)) => opened_at > changed_at,
+            _ => true,
+        }
+    }
+
+    fn contains(&self, alias: &str) -> Result<bool, DbError> {
+        let list = self.inner.lock().map_err(handle_poison_error)?;
+        Ok(list
+            .databases
+            .iter()
+            .any(|(existing, _)| existing.eq_ignore_ascii_case(alias)))
+    }
+
+    fn add(&self, alias: &str, path: &str) -> Result<(), DbError> {
+        let mut list = self.inner.lock().map_err(handle_poison_error)?;
+        list.databases.push((alias.to_string(), path.to_string()));
+        list.changed_at = Some(Instant::now());
+        Ok(())
+    }
+
+    /// Remove an alias, returning whether it was attached
+    fn remove(&self, alias: &str) -> Result<bool, DbError> {
+        let mut list = self.inner.lock().map_err(handle_poison_error)?;
+        let before = list.databases.len();
+        list.databases
+            .retain(|(existing, _)| !existing.eq_ignore_ascii_case(alias));
+        let removed = list.databases.len() != before;
+        if removed {
+            list.changed_at = Some(Instant::now());
+        }
+        Ok(removed)
+    }
+}
+
+/// What to pass to ATTACH to open `path` from a connection to `db_url`
+///
+/// A plain path attached to an in-memory database becomes a URI with an
+/// explicit mode, so the file is opened rather than a new in-memory database.
+fn attach_target(db_url: &str, path: &str) -> String {
+    if !is_in_memory(db_url) || path.starts_with("file:") {
+        return path.to_string();
+    }
+    let escaped = path
+        .replace('%', "%25")
+        .replace('?', "%3f")
+        .replace('#', "%23");
+    format!("file:{}?mode=rwc", escaped)
+}
+
+/// The attachment list for a database URL, created on first use
+pub(super) fn pool_attachments(state: &DbState, db_url: &str) -> Result<Arc<Attachments>, DbError> {
+    let mut attachments = state.attachments.lock().map_err(handle_poison_error)?;
+    Ok(attachments.entry(db_url.to_string()).or_default().clone())
+}
+
+/// Attach another database file to every connection of a pool
+///
+/// Tables in the attached file are then available as `alias.table` in any
+/// query on `db_url`. Connections already open are replaced as they are next
+/// used, and the attachment lasts until detached or the pool is closed.
+#[tauri::command]
+pub async fn attach_database(
+    db_url: String,
+    attach_path: String,
+    alias: String,
+    state: State<'_, DbState>,
+) -> Result<(), DbError> {
+    if alias.eq_ignore_ascii_case("main") || alias.eq_ignore_ascii_case("temp") {
+        return Err(DbError::Invalid(format!(
+            "Cannot attach a database as {}",
+            alias
+        )));
+    }
+
+    let pool = get_or_create_pool(&state, &db_url).await?;
+    let attachments = pool_attachments(&state, &pool_key(&state, &db_url)?)?;
+    if attachments.contains(&alias)? {
+        return Err(DbError::Invalid(format!(
+            "A database is already attached as {}",
+            alias
+        )));
+    }
+
+    // Try it on one connection first so a bad path fails with SQLite's error
+    // here rather than when the pool next opens a connection. An in-memory
+    // database keeps it attached, since its connection is never replaced.
+    let mut conn = pool
+        .acquire()
+        .await
+        .map_err(|e| DbError::connect("Failed to acquire connection", e))?;
+    let target = attach_target(&db_url, &attach_path);
+    let quoted_alias = quote_identifier(&alias);
+    sqlx::query(&format!("ATTACH DATABASE ? AS {}", quoted_alias))
+        .bind(&target)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| DbError::execute("Failed to attach database", e))?;
+    if !is_in_memory(&db_url) {
+        sqlx::query(&format!("DETACH DATABASE {}", quoted_alias))
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| DbError::execute("Failed to attach database", e))?;
+    }
+    drop(conn);
+
+    attachments.add(&alias, &target)?;
+
+    log::info!("Attached {} as {} on {}", attach_path, alias, db_url);
+
+    Ok(())
+}
+
+/// Detach a database previously attached with attach_database
+#[tauri::command]
+pub async fn detach_database(
+    db_url: String,
+    alias: String,
+    state: State<'_, DbState>,
+) -> Result<(), DbError> {
+    let attachments = pool_attachments(&state, &pool_key(&state, &db_url)?)?;
+    if !attachments.remove(&alias)? {
+        return Err(DbError::Invalid(format!(
+            "No database is attached as {}",
+            alias
+        )));
+    }
+
+    if is_in_memory(&db_url) {
+        if let Ok(pool) = get_open_pool(&state, &db_url) {
+            let mut conn = pool
+                .acquire()
+                .await
+                .map_err(|e| DbError::connect("Failed to acquire connection", e))?;
+            sqlx::query(&format!("DETACH DATABASE {}", quote_identifier(&alias)))
+                .execute(&mut *conn)
+                .await
+                .map_err(|e| DbError::execute("Failed to detach database", e))?;
+        }
+    }
+
+    Ok(())
+}
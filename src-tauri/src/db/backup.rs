@@ -0,0 +1,246 @@
+//! Online backup using SQLite's backup API
+//!
+//! The backup copies pages a few at a time and pauses between steps, so
+//! writers on other connections are only blocked for the duration of a step.
+
+use std::ffi::{CStr, CString};
+use std::path::Path;
+use std::ptr::{self, NonNull};
+use std::time::Duration;
+
+use libsqlite3_sys::{
+    sqlite3, sqlite3_backup, sqlite3_backup_finish, sqlite3_backup_init, sqlite3_backup_pagecount,
+    sqlite3_backup_step, sqlite3_close, sqlite3_errcode, sqlite3_errmsg, sqlite3_errstr,
+    sqlite3_open_v2, SQLITE_BUSY, SQLITE_DONE, SQLITE_LOCKED, SQLITE_OK, SQLITE_OPEN_CREATE,
+    SQLITE_OPEN_READWRITE,
+};
+use serde::Serialize;
+use tauri::State;
+
+use super::{get_or_create_pool, DbError, DbState};
+
+/// Pages copied per backup step
+const PAGES_PER_STEP: i32 = 256;
+
+/// Pause between steps so other connections can write
+const STEP_PAUSE: Duration = Duration::from_millis(10);
+
+/// Busy or locked steps in a row before giving up, about five seconds of pauses
+const BUSY_STEP_LIMIT: u32 = 500;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupResult {
+    pub pages_copied: u32,
+}
+
+/// An in-progress backup and the destination connection it writes to
+struct Backup {
+    dest: NonNull<sqlite3>,
+    backup: NonNull<sqlite3_backup>,
+}
+
+// SAFE: SQLite is built thread-safe, and the source connection is locked
+// through sqlx whenever the backup touches it
+unsafe impl Send for Backup {}
+
+impl Backup {
+    /// Open `dest_path` and start a backup of the source's main database into it
+    ///
+    /// # Safety
+    /// `source` must be a valid connection that no other thread is using.
+    unsafe fn start(source: NonNull<sqlite3>, dest_path: &CStr) -> Result<Self, DbError> {
+        let mut dest = ptr::null_mut();
+        let rc = sqlite3_open_v2(
+            dest_path.as_ptr(),
+            &mut dest,
+            SQLITE_OPEN_READWRITE | SQLITE_OPEN_CREATE,
+            ptr::null(),
+        );
+        let Some(dest) = NonNull::new(dest) else {
+            return Err(DbError::Connect {
+                message: format!("Failed to open backup destination: {}", error_str(rc)),
+                code: Some(rc),
+            });
+        };
+        if rc != SQLITE_OK {
+            let message = error_message(dest);
+            sqlite3_close(dest.as_ptr());
+            return Err(DbError::Connect {
+                message: format!("Failed to open backup destination: {}", message),
+                code: Some(rc),
+            });
+        }
+
+        let main = c"main";
+        let backup =
+            sqlite3_backup_init(dest.as_ptr(), main.as_ptr(), source.as_ptr(), main.as_ptr());
+        let Some(backup) = NonNull::new(backup) else {
+            let message = error_message(dest);
+            let code = sqlite3_errcode(dest.as_ptr());
+            sqlite3_close(dest.as_ptr());
+            return Err(DbError::Execute {
+                step: None,
+                message: format!("Failed to start backup: {}", message),
+                code: Some(code),
+            });
+        };
+
+        Ok(Backup { dest, backup })
+    }
+}
+
+impl Drop for Backup {
+    fn drop(&mut self) {
+        unsafe {
+            sqlite3_backup_finish(self.backup.as_ptr());
+            sqlite3_close(self.dest.as_ptr());
+        }
+    }
+}
+
+unsafe fn error_message(handle: NonNull<sqlite3>) -> String {
+    CStr::from_ptr(sqlite3_errmsg(handle.as_ptr()))
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn error_str(rc: i32) -> String {
+    unsafe { CStr::from_ptr(sqlite3_errstr(rc)) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Copy a live database to `dest_path` using SQLite's online backup API
+///
+/// Safe to run while the database is open and in WAL mode, unlike copying
+/// the file on disk. If another connection writes while the backup is
+/// running, SQLite restarts the copy so the result is always consistent.
+///
+/// The copy is written to a temporary file next to `dest_path` and moved
+/// into place once complete, so a failed backup leaves an existing file
+/// intact. Without `overwrite`, a file created at `dest_path` while the
+/// backup ran is kept and the backup fails.
+///
+/// If the database stays busy or locked for about five seconds the backup
+/// gives up with a busy error.
+///
+/// # Arguments
+/// * `db_url` - Database to back up
+/// * `dest_path` - File to write the backup to
+/// * `overwrite` - Replace `dest_path` if it already exists (default false)
+#[tauri::command]
+pub async fn backup_database(
+    db_url: String,
+    dest_path: String,
+    overwrite: Option<bool>,
+    state: State<'_, DbState>,
+) -> Result<BackupResult, DbError> {
+    if !overwrite.unwrap_or(false) && Path::new(&dest_path).exists() {
+        return Err(DbError::Invalid(format!(
+            "Backup destination already exists: {}",
+            dest_path
+        )));
+    }
+
+    let temp_path = format!("{}.{}.partial", dest_path, uuid::Uuid::new_v4());
+    let pages_copied = match back_up_to(&state, &db_url, &temp_path).await {
+        Ok(pages_copied) => pages_copied,
+        Err(e) => {
+            let _ = std::fs::remove_file(&temp_path);
+            return Err(e);
+        }
+    };
+    let moved = if overwrite.unwrap_or(false) {
+        std::fs::rename(&temp_path, &dest_path)
+    } else {
+        // Linking fails instead of replacing a file that appeared meanwhile
+        std::fs::hard_link(&temp_path, &dest_path)
+    };
+    let _ = std::fs::remove_file(&temp_path);
+    match moved {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            return Err(DbError::Invalid(format!(
+                "Backup destination already exists: {}",
+                dest_path
+            )));
+        }
+        Err(e) => {
+            return Err(DbError::Invalid(format!(
+                "Failed to move backup into place: {}",
+                e
+            )));
+        }
+    }
+
+    log::info!(
+        "Backed up {} pages from {} to {}",
+        pages_copied,
+        db_url,
+        dest_path
+    );
+
+    Ok(BackupResult {
+        pages_copied: pages_copied.max(0) as u32,
+    })
+}
+
+/// Run the backup into a new file at `dest_path`, returning the pages copied
+async fn back_up_to(state: &DbState, db_url: &str, dest_path: &str) -> Result<i32, DbError> {
+    let dest = CString::new(dest_path)
+        .map_err(|_| DbError::Invalid("Backup path contains a NUL byte".to_string()))?;
+
+    let pool = get_or_create_pool(state, db_url).await?;
+    let mut conn = pool
+        .acquire()
+        .await
+        .map_err(|e| DbError::connect("Failed to acquire connection", e))?;
+
+    let backup = {
+        let mut handle = conn
+            .lock_handle()
+            .await
+            .map_err(|e| DbError::connect("Failed to lock connection", e))?;
+        unsafe { Backup::start(handle.as_raw_handle(), &dest)? }
+    };
+
+    let mut busy_steps = 0;
+    loop {
+        let rc = {
+            // Hold the sqlx lock so its worker doesn't use the source mid-step
+            let _handle = conn
+                .lock_handle()
+                .await
+                .map_err(|e| DbError::connect("Failed to lock connection", e))?;
+            unsafe { sqlite3_backup_step(backup.backup.as_ptr(), PAGES_PER_STEP) }
+        };
+
+        match rc {
+            SQLITE_DONE => break,
+            SQLITE_OK => busy_steps = 0,
+            SQLITE_BUSY | SQLITE_LOCKED if busy_steps >= BUSY_STEP_LIMIT => {
+                let error = DbError::Execute {
+                    step: None,
+                    message: format!("Backup failed: {}", error_str(rc)),
+                    code: Some(rc),
+                };
+                return Err(error.retries_exhausted(BUSY_STEP_LIMIT));
+            }
+            SQLITE_BUSY | SQLITE_LOCKED => busy_steps += 1,
+            _ => {
+                return Err(DbError::Execute {
+                    step: None,
+                    message: format!("Backup failed: {}", error_str(rc)),
+                    code: Some(rc),
+                })
+            }
+        }
+        tokio::time::sleep(STEP_PAUSE).await;
+    }
+
+    // Closing the destination before it is moved into place, which Windows requires
+    let pages_copied = unsafe { sqlite3_backup_pagecount(backup.backup.as_ptr()) };
+    drop(backup);
+    Ok(pages_copied)
+}
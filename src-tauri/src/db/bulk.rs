@@ -0,0 +1,129 @@
+//! Inserting many rows with few statements
+//!
+//! One INSERT per row spends most of its time on per-statement overhead.
+//! bulk_insert instead packs as many rows into each multi-row `VALUES` list
+//! as SQLite's bound-variable limit allows, all inside one transaction.
+
+use libsqlite3_sys::{sqlite3_limit, SQLITE_LIMIT_VARIABLE_NUMBER};
+use sqlx::{Executor, Statement};
+use tauri::{AppHandle, State};
+
+use super::sql::quote_identifier;
+use super::{bind_params, get_or_create_pool, notify_db_changed, DbError, DbState};
+
+/// The limit SQLite was compiled with before 3.32, used if it can't be read
+const FALLBACK_VARIABLE_LIMIT: usize = 999;
+
+/// Insert rows into a table in as few statements as possible
+///
+/// Every row must have one value per column; values are bound like
+/// execute_transaction parameters. Either all rows are inserted or, if any
+/// statement fails, none are. Emits `db-changed` for the table.
+///
+/// # Returns
+/// The number of rows inserted
+#[tauri::command]
+pub async fn bulk_insert(
+    db_url: String,
+    table: String,
+    columns: Vec<String>,
+    rows: Vec<Vec<serde_json::Value>>,
+    app: AppHandle,
+    state: State<'_, DbState>,
+) -> Result<u64, DbError> {
+    if columns.is_empty() {
+        return Err(DbError::Invalid(
+            "At least one column is required".to_string(),
+        ));
+    }
+    if let Some((index, row)) = rows
+        .iter()
+        .enumerate()
+        .find(|(_, row)| row.len() != columns.len())
+    {
+        return Err(DbError::Invalid(format!(
+            "Row {} has {} values, expected {}",
+            index,
+            row.len(),
+            columns.len()
+        )));
+    }
+    if rows.is_empty() {
+        return Ok(0);
+    }
+
+    let pool = get_or_create_pool(&state, &db_url).await?;
+    let mut tx = pool
+        .begin_with("BEGIN IMMEDIATE")
+        .await
+        .map_err(|e| DbError::begin("Failed to begin transaction", e))?;
+
+    let variable_limit = {
+        let mut handle = tx
+            .lock_handle()
+            .await
+            .map_err(|e| DbError::connect("Failed to lock connection", e))?;
+        let limit = unsafe {
+            sqlite3_limit(
+                handle.as_raw_handle().as_ptr(),
+                SQLITE_LIMIT_VARIABLE_NUMBER,
+                -1,
+            )
+        };
+        usize::try_from(limit)
+            .ok()
+            .filter(|limit| *limit > 0)
+            .unwrap_or(FALLBACK_VARIABLE_LIMIT)
+    };
+    let rows_per_statement = (variable_limit / columns.len()).max(1);
+
+    let insert = format!(
+        "INSERT INTO {} ({}) VALUES ",
+        quote_identifier(&table),
+        columns
+            .iter()
+            .map(|column| quote_identifier(column))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let row_placeholders = format!("({})", vec!["?"; columns.len()].join(", "));
+
+    let mut inserted = 0;
+    let mut first_row = 0;
+    let mut rows = rows.into_iter().peekable();
+    while rows.peek().is_some() {
+        let chunk: Vec<Vec<serde_json::Value>> = rows.by_ref().take(rows_per_statement).collect();
+        let sql = format!(
+            "{}{}",
+            insert,
+            vec![row_placeholders.as_str(); chunk.len()].join(", ")
+        );
+        let failed = |e| {
+            DbError::execute(
+                &format!(
+                    "Failed to insert rows {} to {}",
+                    first_row,
+                    first_row + chunk.len() - 1
+                ),
+                e,
+            )
+        };
+
+        // Every full chunk has the same SQL, so the prepared statement is reused
+        let statement = (&mut *tx).prepare(sql.as_str()).await.map_err(failed)?;
+        let query = bind_params(statement.query(), chunk.iter().flatten().cloned().collect())?;
+        let result = (&mut *tx).execute(query).await.map_err(failed)?;
+
+        inserted += result.rows_affected();
+        first_row += chunk.len();
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| DbError::commit("Failed to commit transaction", e))?;
+
+    log::info!("Bulk inserted {} rows into {}", inserted, table);
+    notify_db_changed(&app, &db_url, [insert.as_str()]);
+
+    Ok(inserted)
+}
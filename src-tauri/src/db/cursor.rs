@@ -0,0 +1,202 @@
+//! Cursors for reading large result sets in pages
+//!
+//! Each cursor runs its query in a background task that streams rows into a
+//! bounded channel, so only a page or so of rows is held in memory at once
+//! and the frontend pulls more as it needs them.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use futures_util::TryStreamExt;
+use serde::Serialize;
+use tauri::async_runtime::JoinHandle;
+use tauri::State;
+use tokio::sync::mpsc;
+
+use super::{
+    bind_params, get_or_create_pool, handle_poison_error, pool_key, row_to_map, DbError, DbState,
+};
+
+/// Rows buffered ahead of the reader before the query pauses
+const CURSOR_BUFFER: usize = 256;
+
+type Row = serde_json::Map<String, serde_json::Value>;
+
+/// Open cursors, keyed by the id handed to the frontend
+#[derive(Default)]
+pub struct CursorState {
+    next_id: AtomicU64,
+    cursors: Mutex<HashMap<u64, Arc<Cursor>>>,
+}
+
+struct Cursor {
+    /// pool_key of the database the query runs on
+    db_key: String,
+    reader: tokio::sync::Mutex<CursorReader>,
+}
+
+struct CursorReader {
+    rows: mpsc::Receiver<Result<Row, DbError>>,
+    task: JoinHandle<()>,
+}
+
+impl Cursor {
+    /// Stop the query, returning its connection to the pool
+    async fn abort(&self) {
+        self.reader.lock().await.task.abort();
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CursorPage {
+    pub rows: Vec<Row>,
+    /// No rows remain; the cursor has been closed
+    pub done: bool,
+}
+
+impl CursorState {
+    fn get(&self, cursor_id: u64) -> Result<Arc<Cursor>, DbError> {
+        let cursors = self.cursors.lock().map_err(handle_poison_error)?;
+        cursors
+            .get(&cursor_id)
+            .cloned()
+            .ok_or_else(|| DbError::Invalid(format!("Cursor not found: {}", cursor_id)))
+    }
+
+    fn remove(&self, cursor_id: u64) -> Result<Option<Arc<Cursor>>, DbError> {
+        let mut cursors = self.cursors.lock().map_err(handle_poison_error)?;
+        Ok(cursors.remove(&cursor_id))
+    }
+
+    /// Stop every cursor's query, releasing their connections
+    pub(super) async fn close_all(&self) -> Result<(), DbError> {
+        let cursors: Vec<_> = {
+            let mut cursors = self.cursors.lock().map_err(handle_poison_error)?;
+            cursors.drain().map(|(_, cursor)| cursor).collect()
+        };
+        for cursor in cursors {
+            cursor.abort().await;
+        }
+        Ok(())
+    }
+
+    /// Stop the queries of the cursors on one database, keyed by its pool_key
+    pub(super) async fn close_database(&self, db_key: &str) -> Result<(), DbError> {
+        let closing: Vec<_> = {
+            let mut cursors = self.cursors.lock().map_err(handle_poison_error)?;
+            let ids: Vec<u64> = cursors
+                .iter()
+                .filter(|(_, cursor)| cursor.db_key == db_key)
+                .map(|(id, _)| *id)
+                .collect();
+            ids.iter().filter_map(|id| cursors.remove(id)).collect()
+        };
+        for cursor in closing {
+            cursor.abort().await;
+        }
+        Ok(())
+    }
+}
+
+/// Start a query and return a cursor id for reading its rows with fetch_next
+///
+/// The query holds a pooled connection (and its read snapshot) until every
+/// row has been fetched or the cursor is closed, so close cursors that are
+/// abandoned early. Bind and query errors are reported by fetch_next.
+#[tauri::command]
+pub async fn open_cursor(
+    db_url: String,
+    sql: String,
+    params: Vec<serde_json::Value>,
+    state: State<'_, DbState>,
+    cursors: State<'_, CursorState>,
+) -> Result<u64, DbError> {
+    let db_key = pool_key(&state, &db_url)?;
+    let pool = get_or_create_pool(&state, &db_url).await?;
+    let (sender, rows) = mpsc::channel(CURSOR_BUFFER);
+
+    let task = tauri::async_runtime::spawn(async move {
+        let query = match bind_params(sqlx::query(&sql), params) {
+            Ok(query) => query,
+            Err(e) => {
+                let _ = sender.send(Err(e)).await;
+                return;
+            }
+        };
+
+        let mut stream = query.fetch(&pool);
+        loop {
+            let row = match stream.try_next().await {
+                Ok(Some(row)) => row_to_map(&row),
+                Ok(None) => break,
+                Err(e) => Err(DbError::execute("Database operation failed", e)),
+            };
+            let failed = row.is_err();
+            // A send error means the cursor was closed
+            if sender.send(row).await.is_err() || failed {
+                break;
+            }
+        }
+    });
+
+    let cursor_id = cursors.next_id.fetch_add(1, Ordering::Relaxed);
+    {
+        let mut open_cursors = cursors.cursors.lock().map_err(handle_poison_error)?;
+        open_cursors.insert(
+            cursor_id,
+            Arc::new(Cursor {
+                db_key,
+                reader: tokio::sync::Mutex::new(CursorReader { rows, task }),
+            }),
+        );
+    }
+
+    Ok(cursor_id)
+}
+
+/// Read up to `count` more rows from a cursor
+///
+/// Once the last row has been read the cursor is closed and `done` is true.
+/// A query error also closes the cursor.
+#[tauri::command]
+pub async fn fetch_next(
+    cursor_id: u64,
+    count: usize,
+    cursors: State<'_, CursorState>,
+) -> Result<CursorPage, DbError> {
+    let cursor = cursors.get(cursor_id)?;
+    let mut cursor = cursor.reader.lock().await;
+
+    let mut rows = Vec::with_capacity(count.min(CURSOR_BUFFER));
+    let mut done = false;
+    while rows.len() < count {
+        match cursor.rows.recv().await {
+            Some(Ok(row)) => rows.push(row),
+            Some(Err(e)) => {
+                cursors.remove(cursor_id)?;
+                return Err(e);
+            }
+            None => {
+                done = true;
+                break;
+            }
+        }
+    }
+
+    if done {
+        cursors.remove(cursor_id)?;
+    }
+
+    Ok(CursorPage { rows, done })
+}
+
+/// Stop a cursor's query and release its connection
+#[tauri::command]
+pub async fn close_cursor(cursor_id: u64, cursors: State<'_, CursorState>) -> Result<(), DbError> {
+    let cursor = cursors
+        .remove(cursor_id)?
+        .ok_or_else(|| DbError::Invalid(format!("Cursor not found: {}", cursor_id)))?;
+    cursor.abort().await;
+    Ok(())
+}
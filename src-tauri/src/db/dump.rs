@@ -0,0 +1,197 @@
+//! Exporting a database as an SQL script
+//!
+//! The script has the same shape as the sqlite3 shell's `.dump`: the tables
+//! and their rows inside one transaction, followed by indexes, triggers and
+//! views, so triggers don't fire while the rows are restored.
+
+use std::fmt::Write as _;
+
+use futures_util::TryStreamExt;
+use sqlx::sqlite::SqliteValueRef;
+use sqlx::{Row, TypeInfo, ValueRef};
+use tauri::State;
+
+use super::sql::quote_identifier;
+use super::{get_or_create_pool, quote_literal, DbError, DbState};
+
+/// Render a column value as an SQL literal
+fn value_literal(value: SqliteValueRef<'_>) -> Result<String, DbError> {
+    if value.is_null() {
+        return Ok("NULL".to_string());
+    }
+
+    let decode_error = |e| DbError::execution(format!("Failed to decode column value: {}", e));
+
+    let type_name = value.type_info().name().to_string();
+    let literal = match type_name.as_str() {
+        "INTEGER" => <i64 as sqlx::Decode<sqlx::Sqlite>>::decode(value)
+            .map_err(decode_error)?
+            .to_string(),
+        "REAL" => {
+            let real = <f64 as sqlx::Decode<sqlx::Sqlite>>::decode(value).map_err(decode_error)?;
+            if real.is_infinite() {
+                // SQLite reads an out-of-range literal as infinity
+                if real > 0.0 { "1e999" } else { "-1e999" }.to_string()
+            } else {
+                // Debug formatting keeps a decimal point and round-trips exactly
+                format!("{:?}", real)
+            }
+        }
+        "BLOB" => {
+            let bytes =
+                <&[u8] as sqlx::Decode<sqlx::Sqlite>>::decode(value).map_err(decode_error)?;
+            let mut literal = String::with_capacity(bytes.len() * 2 + 3);
+            literal.push_str("X'");
+            for byte in bytes {
+                let _ = write!(literal, "{:02X}", byte);
+            }
+            literal.push('\'');
+            literal
+        }
+        _ => quote_literal(
+            &<String as sqlx::Decode<sqlx::Sqlite>>::decode(value).map_err(decode_error)?,
+        ),
+    };
+
+    Ok(literal)
+}
+
+/// Write an SQL script that recreates a database's schema and data
+///
+/// Shadow tables of virtual tables are skipped since creating the virtual
+/// table recreates them; an external-content FTS index is rebuilt from its
+/// base table instead. Generated columns are left for SQLite to compute.
+/// The rows are read in one transaction, so the script is a consistent
+/// snapshot even while other connections write.
+///
+/// # Arguments
+/// * `dest_path` - File to write the script to; when None it is returned instead
+///
+/// # Returns
+/// The script, or None if it was written to `dest_path`
+#[tauri::command]
+pub async fn dump_sql(
+    db_url: String,
+    dest_path: Option<String>,
+    state: State<'_, DbState>,
+) -> Result<Option<String>, DbError> {
+    let pool = get_or_create_pool(&state, &db_url).await?;
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| DbError::begin("Failed to begin transaction", e))?;
+    let read_error = |e| DbError::execute("Failed to dump database", e);
+
+    let objects: Vec<(String, String, String, String)> = sqlx::query_as(
+        "SELECT s.type, s.name, s.sql, coalesce(l.type, '') \
+         FROM sqlite_schema AS s \
+         LEFT JOIN pragma_table_list AS l ON l.schema = 'main' AND l.name = s.name \
+         WHERE s.sql IS NOT NULL AND s.name NOT LIKE 'sqlite\\_%' ESCAPE '\\' \
+         ORDER BY s.rowid",
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(read_error)?;
+
+    let mut script = String::from("PRAGMA foreign_keys=OFF;\nBEGIN TRANSACTION;\n");
+    let mut rebuilds = Vec::new();
+
+    for (_, name, sql, table_kind) in objects.iter().filter(|(kind, ..)| kind == "table") {
+        if table_kind == "shadow" {
+            continue;
+        }
+        script.push_str(sql);
+        script.push_str(";\n");
+
+        let quoted = quote_identifier(name);
+        let options = sql.to_ascii_lowercase().replace(' ', "");
+        if table_kind == "virtual"
+            && options.contains("content=")
+            && !options.contains("content=''")
+        {
+            // The rows live in the base table, which is restored on its own
+            rebuilds.push(format!(
+                "INSERT INTO {}({}) VALUES('rebuild');\n",
+                quoted, quoted
+            ));
+            continue;
+        }
+
+        // Hidden and generated columns can't be inserted into
+        let column_info: Vec<(String, i64)> =
+            sqlx::query_as("SELECT name, hidden FROM pragma_table_xinfo(?)")
+                .bind(name)
+                .fetch_all(&mut *tx)
+                .await
+                .map_err(read_error)?;
+        let columns: Vec<String> = column_info
+            .iter()
+            .filter(|(_, hidden)| *hidden == 0)
+            .map(|(column, _)| quote_identifier(column))
+            .collect();
+        let column_list = columns.join(", ");
+        let insert = if columns.len() == column_info.len() {
+            format!("INSERT INTO {} VALUES(", quoted)
+        } else {
+            format!("INSERT INTO {}({}) VALUES(", quoted, column_list)
+        };
+
+        let select = format!("SELECT {} FROM {}", column_list, quoted);
+        let mut rows = sqlx::query(&select).fetch(&mut *tx);
+        while let Some(row) = rows.try_next().await.map_err(read_error)? {
+            script.push_str(&insert);
+            for index in 0..row.len() {
+                if index > 0 {
+                    script.push(',');
+                }
+                let value = row.try_get_raw(index).map_err(read_error)?;
+                script.push_str(&value_literal(value)?);
+            }
+            script.push_str(");\n");
+        }
+    }
+
+    // AUTOINCREMENT counters, so restored tables don't reuse ids
+    let has_sequence: bool =
+        sqlx::query_scalar("SELECT count(*) > 0 FROM sqlite_schema WHERE name = 'sqlite_sequence'")
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(read_error)?;
+    if has_sequence {
+        let sequences: Vec<(String, i64)> = sqlx::query_as("SELECT name, seq FROM sqlite_sequence")
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(read_error)?;
+        if !sequences.is_empty() {
+            script.push_str("DELETE FROM sqlite_sequence;\n");
+        }
+        for (name, seq) in sequences {
+            let _ = writeln!(
+                script,
+                "INSERT INTO sqlite_sequence VALUES({},{});",
+                quote_literal(&name),
+                seq
+            );
+        }
+    }
+
+    for rebuild in rebuilds {
+        script.push_str(&rebuild);
+    }
+    for (_, _, sql, _) in objects.iter().filter(|(kind, ..)| kind != "table") {
+        script.push_str(sql);
+        script.push_str(";\n");
+    }
+    script.push_str("COMMIT;\n");
+
+    drop(tx);
+
+    let Some(dest_path) = dest_path else {
+        return Ok(Some(script));
+    };
+    tokio::fs::write(&dest_path, script)
+        .await
+        .map_err(|e| DbError::Invalid(format!("Failed to write {}: {}", dest_path, e)))?;
+    log::info!("Dumped {} to {}", db_url, dest_path);
+    Ok(None)
+}
@@ -0,0 +1,280 @@
+//! Errors returned by the database commands
+//!
+//! Each error serializes as `{ kind, message, step, code, primaryCode,
+//! codeName, retries }` so the frontend can tell a connection failure from a
+//! constraint violation without parsing the message, which varies between
+//! SQLite versions. `code` is SQLite's extended result code when the error
+//! came from SQLite, such as 2067 for a UNIQUE constraint, `primaryCode` the
+//! primary code it refines (19, SQLITE_CONSTRAINT) and `codeName` the
+//! extended code's name, such as `SQLITE_CONSTRAINT_UNIQUE`. `retries` is
+//! set for `busy`, the database staying locked through every retry.
+
+use serde::ser::SerializeStruct;
+use serde::Serialize;
+
+#[derive(Debug, thiserror::Error)]
+pub enum DbError {
+    /// Opening the database or acquiring a pooled connection failed
+    #[error("{message}")]
+    Connect { message: String, code: Option<i32> },
+    /// BEGIN failed, usually because the database is busy
+    #[error("{message}")]
+    Begin { message: String, code: Option<i32> },
+    /// A parameter couldn't be bound to a statement
+    #[error("{}bind error: {message}", step_prefix(.step))]
+    Bind {
+        step: Option<usize>,
+        message: String,
+    },
+    /// A statement failed, in step `step` of a transaction if there is one
+    #[error("{}{message}", step_prefix(.step))]
+    Execute {
+        step: Option<usize>,
+        message: String,
+        code: Option<i32>,
+    },
+    /// A step changed a different number of rows than its `expectRowsAffected`
+    #[error("{}expected {expected} rows affected, got {actual}", step_prefix(.step))]
+    Conflict {
+        step: Option<usize>,
+        expected: u64,
+        actual: u64,
+    },
+    /// COMMIT failed; the transaction was rolled back
+    #[error("{message}")]
+    Commit { message: String, code: Option<i32> },
+    /// The request needs something this build or command doesn't support
+    #[error("{0}")]
+    Unsupported(String),
+    /// An argument was invalid, or the database wasn't in the state the command needs
+    #[error("{0}")]
+    Invalid(String),
+    /// The database was still busy after retrying `retries` times
+    #[error("{message} (still busy after {retries} retries)")]
+    Busy {
+        step: Option<usize>,
+        message: String,
+        code: Option<i32>,
+        retries: u32,
+    },
+    #[error("transaction exceeded {timeout_ms} ms")]
+    Timeout { timeout_ms: u64 },
+    #[error("{0}")]
+    Internal(String),
+}
+
+fn step_prefix(step: &Option<usize>) -> String {
+    step.map_or_else(String::new, |index| format!("step {}: ", index))
+}
+
+/// SQLite's extended result code for an error, if SQLite reported one
+pub(super) fn sqlite_code(e: &sqlx::Error) -> Option<i32> {
+    e.as_database_error()
+        .and_then(|db_error| db_error.code())
+        .and_then(|code| code.parse().ok())
+}
+
+/// Name of an SQLite result code, falling back to the primary code's name
+fn code_name(code: i32) -> Option<&'static str> {
+    let name = match code {
+        261 => "SQLITE_BUSY_RECOVERY",
+        517 => "SQLITE_BUSY_SNAPSHOT",
+        773 => "SQLITE_BUSY_TIMEOUT",
+        262 => "SQLITE_LOCKED_SHAREDCACHE",
+        264 => "SQLITE_READONLY_RECOVERY",
+        520 => "SQLITE_READONLY_CANTLOCK",
+        776 => "SQLITE_READONLY_ROLLBACK",
+        1032 => "SQLITE_READONLY_DBMOVED",
+        275 => "SQLITE_CONSTRAINT_CHECK",
+        531 => "SQLITE_CONSTRAINT_COMMITHOOK",
+        787 => "SQLITE_CONSTRAINT_FOREIGNKEY",
+        1043 => "SQLITE_CONSTRAINT_FUNCTION",
+        1299 => "SQLITE_CONSTRAINT_NOTNULL",
+        1555 => "SQLITE_CONSTRAINT_PRIMARYKEY",
+        1811 => "SQLITE_CONSTRAINT_TRIGGER",
+        2067 => "SQLITE_CONSTRAINT_UNIQUE",
+        2323 => "SQLITE_CONSTRAINT_VTAB",
+        2579 => "SQLITE_CONSTRAINT_ROWID",
+        3091 => "SQLITE_CONSTRAINT_DATATYPE",
+        _ => match code & 0xff {
+            1 => "SQLITE_ERROR",
+            2 => "SQLITE_INTERNAL",
+            3 => "SQLITE_PERM",
+            4 => "SQLITE_ABORT",
+            5 => "SQLITE_BUSY",
+            6 => "SQLITE_LOCKED",
+            7 => "SQLITE_NOMEM",
+            8 => "SQLITE_READONLY",
+            9 => "SQLITE_INTERRUPT",
+            10 => "SQLITE_IOERR",
+            11 => "SQLITE_CORRUPT",
+            13 => "SQLITE_FULL",
+            14 => "SQLITE_CANTOPEN",
+            17 => "SQLITE_SCHEMA",
+            18 => "SQLITE_TOOBIG",
+            19 => "SQLITE_CONSTRAINT",
+            20 => "SQLITE_MISMATCH",
+            21 => "SQLITE_MISUSE",
+            23 => "SQLITE_AUTH",
+            25 => "SQLITE_RANGE",
+            26 => "SQLITE_NOTADB",
+            _ => return None,
+        },
+    };
+    Some(name)
+}
+
+const SQLITE_BUSY: i32 = 5;
+const SQLITE_LOCKED: i32 = 6;
+const SQLITE_IOERR: i32 = 10;
+const SQLITE_CANTOPEN: i32 = 14;
+const SQLITE_NOTADB: i32 = 26;
+const SQLITE_READONLY_DBMOVED: i32 = 1032;
+pub(super) const SQLITE_CONSTRAINT_FOREIGNKEY: i32 = 787;
+
+impl DbError {
+    pub(super) fn connect(context: &str, e: sqlx::Error) -> Self {
+        DbError::Connect {
+            code: sqlite_code(&e),
+            message: format!("{}: {}", context, e),
+        }
+    }
+
+    pub(super) fn begin(context: &str, e: sqlx::Error) -> Self {
+        DbError::Begin {
+            code: sqlite_code(&e),
+            message: format!("{}: {}", context, e),
+        }
+    }
+
+    pub(super) fn execute(context: &str, e: sqlx::Error) -> Self {
+        DbError::Execute {
+            step: None,
+            code: sqlite_code(&e),
+            message: format!("{}: {}", context, e),
+        }
+    }
+
+    pub(super) fn commit(context: &str, e: sqlx::Error) -> Self {
+        DbError::Commit {
+            code: sqlite_code(&e),
+            message: format!("{}: {}", context, e),
+        }
+    }
+
+    /// A statement failure that didn't come from SQLite itself, such as a decoding error
+    pub(super) fn execution(message: String) -> Self {
+        DbError::Execute {
+            step: None,
+            message,
+            code: None,
+        }
+    }
+
+    pub(super) fn bind(message: String) -> Self {
+        DbError::Bind {
+            step: None,
+            message,
+        }
+    }
+
+    /// A busy error that is still there after `retries` retries
+    ///
+    /// The message keeps the step prefix of the original error.
+    pub(super) fn retries_exhausted(self, retries: u32) -> Self {
+        DbError::Busy {
+            step: self.step(),
+            code: self.code(),
+            message: self.to_string(),
+            retries,
+        }
+    }
+
+    /// Attribute a bind or execute error to a transaction step
+    pub(super) fn in_step(mut self, index: usize) -> Self {
+        if let DbError::Bind { step, .. }
+        | DbError::Execute { step, .. }
+        | DbError::Conflict { step, .. }
+        | DbError::Busy { step, .. } = &mut self
+        {
+            *step = Some(index);
+        }
+        self
+    }
+
+    /// The transaction step the error happened in, if any
+    pub fn step(&self) -> Option<usize> {
+        match self {
+            DbError::Bind { step, .. }
+            | DbError::Execute { step, .. }
+            | DbError::Conflict { step, .. }
+            | DbError::Busy { step, .. } => *step,
+            _ => None,
+        }
+    }
+
+    /// SQLite's extended result code, if the error came from SQLite
+    pub fn code(&self) -> Option<i32> {
+        match self {
+            DbError::Connect { code, .. }
+            | DbError::Begin { code, .. }
+            | DbError::Execute { code, .. }
+            | DbError::Commit { code, .. }
+            | DbError::Busy { code, .. } => *code,
+            _ => None,
+        }
+    }
+
+    /// Whether SQLite reported SQLITE_BUSY or SQLITE_LOCKED (including extended codes)
+    pub fn is_busy(&self) -> bool {
+        self.code()
+            .is_some_and(|code| matches!(code & 0xff, SQLITE_BUSY | SQLITE_LOCKED))
+    }
+
+    /// Whether SQLite reported the database file itself as unusable, rather than the SQL failing
+    ///
+    /// These are I/O errors, a file that can't be opened or isn't a database,
+    /// and a file that was moved or deleted while open.
+    pub fn is_connection_error(&self) -> bool {
+        self.code().is_some_and(|code| {
+            code == SQLITE_READONLY_DBMOVED
+                || matches!(code & 0xff, SQLITE_IOERR | SQLITE_CANTOPEN | SQLITE_NOTADB)
+        })
+    }
+}
+
+impl Serialize for DbError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let kind = match self {
+            DbError::Connect { .. } => "connect",
+            DbError::Begin { .. } => "begin",
+            DbError::Bind { .. } => "bind",
+            DbError::Execute { .. } => "execute",
+            DbError::Conflict { .. } => "conflict",
+            DbError::Commit { .. } => "commit",
+            DbError::Unsupported(_) => "unsupported",
+            DbError::Invalid(_) => "invalid",
+            DbError::Busy { .. } => "busy",
+            DbError::Timeout { .. } => "timeout",
+            DbError::Internal(_) => "internal",
+        };
+        let code = self.code();
+        let retries = match self {
+            DbError::Busy { retries, .. } => Some(*retries),
+            _ => None,
+        };
+
+        let mut state = serializer.serialize_struct("DbError", 7)?;
+        state.serialize_field("kind", kind)?;
+        state.serialize_field("message", &self.to_string())?;
+        state.serialize_field("step", &self.step())?;
+        state.serialize_field("code", &code)?;
+        state.serialize_field("primaryCode", &code.map(|code| code & 0xff))?;
+        state.serialize_field("codeName", &code.and_then(code_name))?;
+        state.serialize_field("retries", &retries)?;
+        state.end()
+    }
+}
@@ -0,0 +1,224 @@
+//! Full-text search over existing tables with FTS5
+//!
+//! An index is an external-content FTS5 table named `<table>_fts`, kept in
+//! sync with its base table by triggers, so rows are only stored once.
+
+use serde::Serialize;
+use sqlx::{Column, Executor, Row};
+use tauri::State;
+
+use super::sql::quote_identifier;
+use super::{get_or_create_pool, value_to_json, DbError, DbState};
+
+/// Matches returned by search_fts when no limit is given
+const DEFAULT_SEARCH_LIMIT: u32 = 50;
+
+#[derive(Debug, Serialize)]
+pub struct FtsMatch {
+    pub rowid: i64,
+    /// FTS5 bm25 rank; lower is a better match
+    pub rank: f64,
+    /// Matching excerpt with terms wrapped in `<mark>`; the text itself is not HTML-escaped
+    pub snippet: String,
+    /// The matching row of the base table
+    pub row: serde_json::Map<String, serde_json::Value>,
+}
+
+fn fts_table(table: &str) -> String {
+    format!("{}_fts", table)
+}
+
+/// Fail unless `table` is an existing table with all of `columns`
+async fn validate_table(
+    pool: &sqlx::SqlitePool,
+    table: &str,
+    columns: &[String],
+) -> Result<(), DbError> {
+    let existing: Vec<String> = sqlx::query("SELECT name FROM pragma_table_info(?)")
+        .bind(table)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| DbError::execute(&format!("Failed to read table {}", table), e))?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+
+    if existing.is_empty() {
+        return Err(DbError::Invalid(format!("Table does not exist: {}", table)));
+    }
+
+    let missing: Vec<&str> = columns
+        .iter()
+        .filter(|column| !existing.contains(column))
+        .map(String::as_str)
+        .collect();
+    if !missing.is_empty() {
+        return Err(DbError::Invalid(format!(
+            "Table {} has no columns: {}",
+            table,
+            missing.join(", ")
+        )));
+    }
+
+    Ok(())
+}
+
+/// Turn user input into an FTS5 query that matches rows containing every word
+///
+/// Each word is quoted so FTS5 operators and column filters in the input are
+/// searched for literally; a trailing `*` is kept as a prefix search.
+fn escape_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .filter_map(|term| {
+            let (word, prefix) = match term.strip_suffix('*') {
+                Some(word) => (word, "*"),
+                None => (term, ""),
+            };
+            (!word.is_empty()).then(|| format!("\"{}\"{}", word.replace('"', "\"\""), prefix))
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Create a full-text index over columns of a table
+///
+/// Creates the FTS5 table `<table>_fts` and insert, update and delete
+/// triggers that keep it in sync, then indexes the rows already in the
+/// table. The base table must be a rowid table.
+#[tauri::command]
+pub async fn create_fts_index(
+    db_url: String,
+    table: String,
+    columns: Vec<String>,
+    state: State<'_, DbState>,
+) -> Result<(), DbError> {
+    if columns.is_empty() {
+        return Err(DbError::Invalid(
+            "At least one column is required".to_string(),
+        ));
+    }
+
+    let pool = get_or_create_pool(&state, &db_url).await?;
+    validate_table(&pool, &table, &columns).await?;
+
+    let base = quote_identifier(&table);
+    let fts = quote_identifier(&fts_table(&table));
+    let quoted: Vec<String> = columns.iter().map(|c| quote_identifier(c)).collect();
+    let column_list = quoted.join(", ");
+    let values = |prefix: &str| {
+        quoted
+            .iter()
+            .map(|c| format!("{}.{}", prefix, c))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let trigger = |suffix: &str| quote_identifier(&format!("{}_fts_{}", table, suffix));
+
+    let statements = [
+        format!(
+            "CREATE VIRTUAL TABLE {fts} USING fts5({column_list}, content={}, content_rowid='rowid')",
+            super::quote_literal(&table)
+        ),
+        format!(
+            "CREATE TRIGGER {} AFTER INSERT ON {base} BEGIN \
+             INSERT INTO {fts}(rowid, {column_list}) VALUES (new.rowid, {}); END",
+            trigger("ai"),
+            values("new")
+        ),
+        format!(
+            "CREATE TRIGGER {} AFTER DELETE ON {base} BEGIN \
+             INSERT INTO {fts}({fts}, rowid, {column_list}) VALUES ('delete', old.rowid, {}); END",
+            trigger("ad"),
+            values("old")
+        ),
+        format!(
+            "CREATE TRIGGER {} AFTER UPDATE ON {base} BEGIN \
+             INSERT INTO {fts}({fts}, rowid, {column_list}) VALUES ('delete', old.rowid, {}); \
+             INSERT INTO {fts}(rowid, {column_list}) VALUES (new.rowid, {}); END",
+            trigger("au"),
+            values("old"),
+            values("new")
+        ),
+        format!("INSERT INTO {fts}({fts}) VALUES ('rebuild')"),
+    ];
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| DbError::begin("Failed to begin transaction", e))?;
+    for statement in &statements {
+        (&mut *tx)
+            .execute(statement.as_str())
+            .await
+            .map_err(|e| DbError::execute("Failed to create full-text index", e))?;
+    }
+    tx.commit()
+        .await
+        .map_err(|e| DbError::commit("Failed to commit transaction", e))?;
+
+    log::info!(
+        "Created full-text index on {}({})",
+        table,
+        columns.join(", ")
+    );
+
+    Ok(())
+}
+
+/// Search a table's full-text index, best matches first
+///
+/// `query` is plain user input: words are matched literally (all must
+/// appear) and a trailing `*` matches by prefix.
+///
+/// # Arguments
+/// * `table` - Base table passed to create_fts_index
+/// * `limit` - Maximum matches to return (default 50)
+#[tauri::command]
+pub async fn search_fts(
+    db_url: String,
+    table: String,
+    query: String,
+    limit: Option<u32>,
+    state: State<'_, DbState>,
+) -> Result<Vec<FtsMatch>, DbError> {
+    let match_query = escape_query(&query);
+    if match_query.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let pool = get_or_create_pool(&state, &db_url).await?;
+    validate_table(&pool, &table, &[]).await?;
+
+    let base = quote_identifier(&table);
+    let fts = quote_identifier(&fts_table(&table));
+    let sql = format!(
+        "SELECT {fts}.rowid, {fts}.rank, snippet({fts}, -1, '<mark>', '</mark>', '…', 16), {base}.* \
+         FROM {fts} JOIN {base} ON {base}.rowid = {fts}.rowid \
+         WHERE {fts} MATCH ? ORDER BY {fts}.rank LIMIT ?"
+    );
+
+    let rows = sqlx::query(&sql)
+        .bind(match_query)
+        .bind(limit.unwrap_or(DEFAULT_SEARCH_LIMIT))
+        .fetch_all(&pool)
+        .await
+        .map_err(|e| DbError::execute("Full-text search failed", e))?;
+
+    let read = |e: sqlx::Error| DbError::execute("Failed to read search result", e);
+    rows.iter()
+        .map(|row| {
+            let mut base_row = serde_json::Map::new();
+            for (i, column) in row.columns().iter().enumerate().skip(3) {
+                let value = row.try_get_raw(i).map_err(read)?;
+                base_row.insert(column.name().to_string(), value_to_json(value)?);
+            }
+            Ok(FtsMatch {
+                rowid: row.try_get(0).map_err(read)?,
+                rank: row.try_get(1).map_err(read)?,
+                snippet: row.try_get(2).map_err(read)?,
+                row: base_row,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,167 @@
+//! Scalar SQL functions implemented in Rust
+//!
+//! Every function in [`FUNCTIONS`] is registered on each connection the pool
+//! opens, so queries can call them regardless of which connection they run
+//! on. To add one, write an `extern "C"` implementation and list it below.
+
+use std::ffi::{c_char, c_int, CStr};
+use std::ptr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use libsqlite3_sys::{
+    sqlite3, sqlite3_context, sqlite3_create_function_v2, sqlite3_errstr, sqlite3_result_int64,
+    sqlite3_result_null, sqlite3_result_text, sqlite3_value, sqlite3_value_bytes,
+    sqlite3_value_text, sqlite3_value_type, SQLITE_DETERMINISTIC, SQLITE_INNOCUOUS, SQLITE_NULL,
+    SQLITE_OK, SQLITE_TRANSIENT, SQLITE_UTF8,
+};
+use sqlx::SqliteConnection;
+
+type ScalarFn = unsafe extern "C" fn(*mut sqlite3_context, c_int, *mut *mut sqlite3_value);
+
+struct ScalarFunction {
+    name: &'static CStr,
+    arg_count: c_int,
+    /// Same inputs always give the same output, so SQLite may cache and index it
+    deterministic: bool,
+    implementation: ScalarFn,
+}
+
+const FUNCTIONS: &[ScalarFunction] = &[
+    // uuid() -> random version 4 UUID as text
+    ScalarFunction {
+        name: c"uuid",
+        arg_count: 0,
+        deterministic: false,
+        implementation: uuid_v4,
+    },
+    // now_ms() -> milliseconds since the Unix epoch
+    ScalarFunction {
+        name: c"now_ms",
+        arg_count: 0,
+        deterministic: false,
+        implementation: now_ms,
+    },
+    // slugify(text) -> lowercase words joined by hyphens
+    ScalarFunction {
+        name: c"slugify",
+        arg_count: 1,
+        deterministic: true,
+        implementation: slugify,
+    },
+];
+
+/// Register every built-in function on a new connection
+pub(super) async fn register_functions(conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+    let mut handle = conn.lock_handle().await?;
+    let db = handle.as_raw_handle().as_ptr();
+
+    for function in FUNCTIONS {
+        unsafe { register(db, function) }.map_err(|message| {
+            sqlx::Error::Configuration(
+                format!(
+                    "Failed to register SQL function {}: {}",
+                    function.name.to_string_lossy(),
+                    message
+                )
+                .into(),
+            )
+        })?;
+    }
+
+    Ok(())
+}
+
+unsafe fn register(db: *mut sqlite3, function: &ScalarFunction) -> Result<(), String> {
+    let mut flags = SQLITE_UTF8;
+    if function.deterministic {
+        flags |= SQLITE_DETERMINISTIC | SQLITE_INNOCUOUS;
+    }
+
+    let rc = sqlite3_create_function_v2(
+        db,
+        function.name.as_ptr(),
+        function.arg_count,
+        flags,
+        ptr::null_mut(),
+        Some(function.implementation),
+        None,
+        None,
+        None,
+    );
+
+    if rc == SQLITE_OK {
+        Ok(())
+    } else {
+        Err(CStr::from_ptr(sqlite3_errstr(rc))
+            .to_string_lossy()
+            .into_owned())
+    }
+}
+
+unsafe fn result_text(ctx: *mut sqlite3_context, text: &str) {
+    sqlite3_result_text(
+        ctx,
+        text.as_ptr() as *const c_char,
+        text.len() as c_int,
+        SQLITE_TRANSIENT(),
+    );
+}
+
+/// Read an argument as text, or None if it is NULL
+unsafe fn arg_text(value: *mut sqlite3_value) -> Option<String> {
+    if sqlite3_value_type(value) == SQLITE_NULL {
+        return None;
+    }
+
+    let text = sqlite3_value_text(value);
+    if text.is_null() {
+        return Some(String::new());
+    }
+    let len = sqlite3_value_bytes(value) as usize;
+    let bytes = std::slice::from_raw_parts(text, len);
+    Some(String::from_utf8_lossy(bytes).into_owned())
+}
+
+unsafe extern "C" fn uuid_v4(
+    ctx: *mut sqlite3_context,
+    _argc: c_int,
+    _argv: *mut *mut sqlite3_value,
+) {
+    result_text(ctx, &uuid::Uuid::new_v4().to_string());
+}
+
+unsafe extern "C" fn now_ms(
+    ctx: *mut sqlite3_context,
+    _argc: c_int,
+    _argv: *mut *mut sqlite3_value,
+) {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as i64);
+    sqlite3_result_int64(ctx, millis);
+}
+
+unsafe extern "C" fn slugify(
+    ctx: *mut sqlite3_context,
+    _argc: c_int,
+    argv: *mut *mut sqlite3_value,
+) {
+    let Some(text) = arg_text(*argv) else {
+        sqlite3_result_null(ctx);
+        return;
+    };
+
+    let mut slug = String::with_capacity(text.len());
+    for c in text.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    if slug.ends_with('-') {
+        slug.pop();
+    }
+
+    result_text(ctx, &slug);
+}
@@ -0,0 +1,275 @@
+//! Restoring a database from an SQL script
+//!
+//! The script is read in chunks and split into statements as it arrives, so
+//! a large dump doesn't have to fit in memory at once. A statement that
+//! spans a chunk boundary waits for the next chunk before it runs.
+
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use sqlx::{Connection, Executor, Row, SqliteConnection};
+use tauri::{AppHandle, Emitter, State};
+use tokio::io::AsyncReadExt;
+
+use super::error::SQLITE_CONSTRAINT_FOREIGNKEY;
+use super::{
+    get_or_create_pool, notify_tables_changed, sql, BatchResult, DbError, DbState,
+    PROGRESS_INTERVAL,
+};
+
+/// Event import_sql reports ImportProgress on
+const IMPORT_PROGRESS_EVENT: &str = "db-import-progress";
+/// Bytes read from the script at a time
+const CHUNK_SIZE: usize = 64 * 1024;
+/// An import that takes at least this long ends with a system notification
+const NOTIFY_AFTER: Duration = Duration::from_secs(10);
+
+/// Payload of import_sql progress events
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportProgress {
+    pub bytes_read: u64,
+    /// Size of the script file; None if it couldn't be read
+    pub total_bytes: Option<u64>,
+    pub statements_executed: usize,
+}
+
+/// Whether a statement would start or end a transaction
+///
+/// Scripts from dump_sql wrap their rows in BEGIN/COMMIT, which would fail
+/// inside the transaction the import already runs in.
+fn is_transaction_control(statement: &str) -> bool {
+    let mut words = statement
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .filter(|word| !word.is_empty());
+    match words.next() {
+        Some(word) => {
+            ["BEGIN", "COMMIT", "END"]
+                .iter()
+                .any(|keyword| word.eq_ignore_ascii_case(keyword))
+                || (word.eq_ignore_ascii_case("ROLLBACK")
+                    && !words.any(|word| word.eq_ignore_ascii_case("TO")))
+        }
+        None => false,
+    }
+}
+
+/// Run an SQL script file against a database in one transaction
+///
+/// Statements are split the same way as execute_batch, so multi-line
+/// statements, trigger bodies and semicolons inside string literals are
+/// handled. The script's own BEGIN, COMMIT and ROLLBACK statements are
+/// skipped. Like the sqlite3 shell's dumps expect, foreign keys aren't
+/// enforced while the script runs, so tables and rows can come in any order;
+/// they are checked once before committing instead. If a statement or that
+/// check fails, nothing the script did is kept and the result says why.
+///
+/// Emits `db-import-progress` every 100 statements and once at the end, and
+/// `db-changed` for the tables written once the import is committed. An
+/// import that takes 10 seconds or more also shows a system notification
+/// when it finishes.
+#[tauri::command]
+pub async fn import_sql(
+    db_url: String,
+    path: String,
+    app: AppHandle,
+    state: State<'_, DbState>,
+) -> Result<BatchResult, DbError> {
+    let file = tokio::fs::File::open(&path)
+        .await
+        .map_err(|e| DbError::Invalid(format!("Failed to read {}: {}", path, e)))?;
+
+    let pool = get_or_create_pool(&state, &db_url).await?;
+    let mut conn = pool
+        .acquire()
+        .await
+        .map_err(|e| DbError::connect("Failed to acquire connection", e))?;
+
+    // foreign_keys can't change inside a transaction, so it is switched off
+    // on this connection around the whole import
+    let foreign_keys: bool = sqlx::query_scalar("PRAGMA foreign_keys")
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| DbError::execute("Failed to read foreign_keys", e))?;
+    if foreign_keys {
+        (&mut *conn)
+            .execute("PRAGMA foreign_keys = OFF")
+            .await
+            .map_err(|e| DbError::execute("Failed to disable foreign keys", e))?;
+    }
+
+    let started = Instant::now();
+    let result = run_script(&mut conn, file, &path, &app, &db_url).await;
+
+    if foreign_keys {
+        if let Err(e) = (&mut *conn).execute("PRAGMA foreign_keys = ON").await {
+            // Don't hand a connection without foreign keys back to the pool
+            log::warn!("Failed to re-enable foreign keys after import: {}", e);
+            conn.close_on_drop();
+        }
+    }
+
+    if started.elapsed() >= NOTIFY_AFTER {
+        let file_name = std::path::Path::new(&path)
+            .file_name()
+            .map_or(path.clone(), |name| name.to_string_lossy().into_owned());
+        match &result {
+            Ok(batch) if batch.success => crate::notifications::notify(
+                &app,
+                "Import finished",
+                &format!(
+                    "Ran {} statements from {}",
+                    batch.statements_executed, file_name
+                ),
+            ),
+            _ => crate::notifications::notify(
+                &app,
+                "Import failed",
+                &format!("Nothing from {} was imported", file_name),
+            ),
+        }
+    }
+
+    result
+}
+
+/// Execute every statement of a script in one transaction on `conn`
+async fn run_script(
+    conn: &mut SqliteConnection,
+    mut file: tokio::fs::File,
+    path: &str,
+    app: &AppHandle,
+    db_url: &str,
+) -> Result<BatchResult, DbError> {
+    let read_error =
+        |e: std::io::Error| DbError::Invalid(format!("Failed to read {}: {}", path, e));
+    let total_bytes = file.metadata().await.map(|metadata| metadata.len()).ok();
+
+    // Take the write lock up front, so a busy database fails before any work
+    let mut tx = conn
+        .begin_with("BEGIN IMMEDIATE")
+        .await
+        .map_err(|e| DbError::begin("Failed to begin transaction", e))?;
+
+    let progress = |bytes_read, statements_executed| {
+        let _ = app.emit(
+            IMPORT_PROGRESS_EVENT,
+            ImportProgress {
+                bytes_read,
+                total_bytes,
+                statements_executed,
+            },
+        );
+    };
+
+    // Text read but not yet run, and bytes of a character split across chunks
+    let mut pending = sql::ScriptSplitter::default();
+    let mut undecoded = Vec::new();
+    let mut chunk = vec![0; CHUNK_SIZE];
+    let mut bytes_read = 0;
+    let mut statement_index = 0;
+    let mut statements_executed = 0;
+    let mut tables: Vec<String> = Vec::new();
+
+    loop {
+        let read = file.read(&mut chunk).await.map_err(read_error)?;
+        let at_end = read == 0;
+        bytes_read += read as u64;
+
+        undecoded.extend_from_slice(&chunk[..read]);
+        let valid = match std::str::from_utf8(&undecoded) {
+            Ok(text) => text.len(),
+            Err(e) if e.error_len().is_none() && !at_end => e.valid_up_to(),
+            Err(_) => {
+                return Err(DbError::Invalid(format!(
+                    "{} is not valid UTF-8 text",
+                    path
+                )))
+            }
+        };
+        pending.push_str(&String::from_utf8_lossy(&undecoded[..valid]));
+        undecoded.drain(..valid);
+
+        for statement in pending.take_statements(at_end) {
+            if is_transaction_control(&statement) {
+                statement_index += 1;
+                continue;
+            }
+            if let Err(e) = (&mut *tx).execute(statement.as_str()).await {
+                let _ = tx.rollback().await;
+                let error = DbError::execute(&format!("statement {}", statement_index), e);
+                return Ok(BatchResult {
+                    success: false,
+                    error: Some(error.to_string()),
+                    error_detail: Some(error),
+                    statements_executed,
+                    failed_statement: Some(statement),
+                });
+            }
+            if let Some(table) = sql::written_table(&statement) {
+                if !tables.contains(&table) {
+                    tables.push(table);
+                }
+            }
+            statement_index += 1;
+            statements_executed += 1;
+            if statements_executed % PROGRESS_INTERVAL == 0 {
+                progress(bytes_read, statements_executed);
+            }
+        }
+
+        if at_end {
+            break;
+        }
+    }
+
+    let violations = sqlx::query("PRAGMA foreign_key_check")
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| DbError::execute("Foreign key check failed", e))?;
+    if let Some(row) = violations.first() {
+        let table: String = row.get("table");
+        let rowid: Option<i64> = row.get("rowid");
+        let parent: String = row.get("parent");
+        let _ = tx.rollback().await;
+        let error = DbError::Execute {
+            step: None,
+            message: format!(
+                "{} of {} references a missing row in {} ({} foreign key violations)",
+                rowid.map_or("A row".to_string(), |rowid| format!("Row {}", rowid)),
+                table,
+                parent,
+                violations.len()
+            ),
+            code: Some(SQLITE_CONSTRAINT_FOREIGNKEY),
+        };
+        return Ok(BatchResult {
+            success: false,
+            error: Some(error.to_string()),
+            error_detail: Some(error),
+            statements_executed,
+            failed_statement: None,
+        });
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| DbError::commit("Failed to commit import", e))?;
+
+    progress(bytes_read, statements_executed);
+    log::info!(
+        "Imported {} statements from {} into {}",
+        statements_executed,
+        path,
+        db_url
+    );
+    notify_tables_changed(app, db_url, tables);
+
+    Ok(BatchResult {
+        success: true,
+        error: None,
+        error_detail: None,
+        statements_executed,
+        failed_statement: None,
+    })
+}
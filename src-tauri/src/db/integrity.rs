@@ -0,0 +1,60 @@
+//! Checking a database file for corruption
+
+use sqlx::Row;
+use tauri::State;
+
+use super::{get_or_create_pool, DbError, DbState};
+
+/// Check a database for corruption and broken foreign key references
+///
+/// Runs `PRAGMA integrity_check` and, unless `foreign_keys` is false,
+/// `PRAGMA foreign_key_check`. Returns `["ok"]` if no problems were found,
+/// otherwise one message per problem; integrity_check stops after its first
+/// 100 problems.
+#[tauri::command]
+pub async fn check_integrity(
+    db_url: String,
+    foreign_keys: Option<bool>,
+    state: State<'_, DbState>,
+) -> Result<Vec<String>, DbError> {
+    let pool = get_or_create_pool(&state, &db_url).await?;
+
+    let mut problems: Vec<String> = sqlx::query("PRAGMA integrity_check")
+        .fetch_all(&pool)
+        .await
+        .map_err(|e| DbError::execute("Integrity check failed", e))?
+        .iter()
+        .map(|row| row.get::<String, _>(0))
+        .filter(|message| message != "ok")
+        .collect();
+
+    if foreign_keys.unwrap_or(true) {
+        let rows = sqlx::query("PRAGMA foreign_key_check")
+            .fetch_all(&pool)
+            .await
+            .map_err(|e| DbError::execute("Foreign key check failed", e))?;
+        for row in rows {
+            let table: String = row.get("table");
+            let rowid: Option<i64> = row.get("rowid");
+            let parent: String = row.get("parent");
+            problems.push(match rowid {
+                Some(rowid) => format!(
+                    "Row {} of {} references a missing row in {}",
+                    rowid, table, parent
+                ),
+                None => format!("A row of {} references a missing row in {}", table, parent),
+            });
+        }
+    }
+
+    if problems.is_empty() {
+        return Ok(vec!["ok".to_string()]);
+    }
+
+    log::warn!(
+        "Integrity check found {} problems in {}",
+        problems.len(),
+        db_url
+    );
+    Ok(problems)
+}
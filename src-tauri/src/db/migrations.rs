@@ -0,0 +1,95 @@
+//! Versioned schema migrations
+//!
+//! The applied version is tracked in SQLite's `PRAGMA user_version`, so no
+//! bookkeeping table is needed and the version travels with the file.
+
+use serde::Deserialize;
+use sqlx::{Executor, Row};
+use tauri::State;
+
+use super::{get_or_create_pool, sql, sqlite_code, DbError, DbState};
+
+#[derive(Debug, Deserialize)]
+pub struct Migration {
+    pub version: i64,
+    pub sql: String,
+}
+
+/// Apply every migration newer than the database's user_version
+///
+/// Pending migrations run in version order inside a single transaction, with
+/// user_version bumped after each one, so either all of them apply or none
+/// do. Running again with the same migrations is a no-op.
+///
+/// # Returns
+/// The versions that were applied, in order
+#[tauri::command]
+pub async fn run_migrations(
+    db_url: String,
+    mut migrations: Vec<Migration>,
+    state: State<'_, DbState>,
+) -> Result<Vec<i64>, DbError> {
+    migrations.sort_by_key(|migration| migration.version);
+    if let Some(pair) = migrations
+        .windows(2)
+        .find(|pair| pair[0].version == pair[1].version)
+    {
+        return Err(DbError::Invalid(format!(
+            "Duplicate migration version: {}",
+            pair[0].version
+        )));
+    }
+
+    let pool = get_or_create_pool(&state, &db_url).await?;
+
+    // IMMEDIATE takes the write lock up front so two windows can't both
+    // read the same user_version and apply the same migrations
+    let mut tx = pool
+        .begin_with("BEGIN IMMEDIATE")
+        .await
+        .map_err(|e| DbError::begin("Failed to begin transaction", e))?;
+
+    let current_version: i64 = sqlx::query("PRAGMA user_version")
+        .fetch_one(&mut *tx)
+        .await
+        .and_then(|row| row.try_get(0))
+        .map_err(|e| DbError::execute("Failed to read user_version", e))?;
+
+    let mut applied = Vec::new();
+    for migration in migrations
+        .iter()
+        .filter(|migration| migration.version > current_version)
+    {
+        for statement in sql::split_statements(&migration.sql) {
+            (&mut *tx)
+                .execute(statement)
+                .await
+                .map_err(|e| DbError::Execute {
+                    step: None,
+                    code: sqlite_code(&e),
+                    message: format!(
+                        "Migration {} failed: {} | Statement: {}",
+                        migration.version, e, statement
+                    ),
+                })?;
+        }
+
+        // PRAGMA values can't be bound as parameters; the version is an integer
+        (&mut *tx)
+            .execute(format!("PRAGMA user_version = {}", migration.version).as_str())
+            .await
+            .map_err(|e| DbError::execute("Failed to set user_version", e))?;
+
+        applied.push(migration.version);
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| DbError::commit("Failed to commit migrations", e))?;
+
+    if !applied.is_empty() {
+        log::info!("Applied migrations {:?} to {}", applied, db_url);
+    }
+
+    Ok(applied)
+}
@@ -0,0 +1,178 @@
+//! An optional in-memory log of the statements execute_transaction runs
+//!
+//! The log is off by default. While it is off, the only cost
+//! execute_transaction pays is one atomic load per transaction. Turned on, it
+//! keeps the most recent entries in a ring buffer. Bound values are redacted
+//! unless asked for, since they often hold user data.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::State;
+
+use super::{handle_poison_error, DbError, DbState, StepResult};
+
+/// Entries kept unless set_query_log chooses another capacity
+const DEFAULT_CAPACITY: usize = 1000;
+
+/// One statement execute_transaction ran
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryLogEntry {
+    pub db_url: String,
+    pub sql: String,
+    pub param_count: usize,
+    /// The bound values; only recorded when the log was turned on with includeParams
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub params: Option<Vec<serde_json::Value>>,
+    pub duration_micros: u64,
+    pub rows_affected: u64,
+    /// When the statement started, as RFC 3339 in UTC
+    pub timestamp: String,
+    /// Why the statement failed, if it did
+    pub error: Option<String>,
+}
+
+struct Buffer {
+    entries: VecDeque<QueryLogEntry>,
+    capacity: usize,
+    include_params: bool,
+}
+
+/// Recent statements, kept in DbState
+pub struct QueryLog {
+    enabled: AtomicBool,
+    buffer: Mutex<Buffer>,
+}
+
+impl Default for QueryLog {
+    fn default() -> Self {
+        QueryLog {
+            enabled: AtomicBool::new(false),
+            buffer: Mutex::new(Buffer {
+                entries: VecDeque::new(),
+                capacity: DEFAULT_CAPACITY,
+                include_params: false,
+            }),
+        }
+    }
+}
+
+impl QueryLog {
+    /// A recorder for statements run against `db_url`, or None while the log is off
+    pub(super) fn recorder<'a>(&'a self, db_url: &'a str) -> Option<Recorder<'a>> {
+        self.enabled
+            .load(Ordering::Relaxed)
+            .then_some(Recorder { log: self, db_url })
+    }
+}
+
+/// Adds the statements of one transaction to the log
+pub(super) struct Recorder<'a> {
+    log: &'a QueryLog,
+    db_url: &'a str,
+}
+
+impl Recorder<'_> {
+    pub(super) fn record(
+        &self,
+        sql: &str,
+        params: &[serde_json::Value],
+        duration: Duration,
+        outcome: Result<&StepResult, &DbError>,
+    ) {
+        // A poisoned log shouldn't fail the transaction it describes
+        let Ok(mut buffer) = self.log.buffer.lock() else {
+            return;
+        };
+
+        let started = chrono::Utc::now()
+            - chrono::Duration::from_std(duration).unwrap_or(chrono::Duration::zero());
+        let (rows_affected, error) = match outcome {
+            Ok(result) => (result.rows_affected, result.error.clone()),
+            Err(error) => (0, Some(error.to_string())),
+        };
+        let entry = QueryLogEntry {
+            db_url: self.db_url.to_string(),
+            sql: sql.to_string(),
+            param_count: params.len(),
+            params: buffer.include_params.then(|| params.to_vec()),
+            duration_micros: u64::try_from(duration.as_micros()).unwrap_or(u64::MAX),
+            rows_affected,
+            timestamp: started.to_rfc3339_opts(chrono::SecondsFormat::Micros, true),
+            error,
+        };
+
+        if buffer.entries.len() >= buffer.capacity {
+            buffer.entries.pop_front();
+        }
+        buffer.entries.push_back(entry);
+    }
+}
+
+/// Turn the query log on or off
+///
+/// Every execute_transaction step is recorded while the log is on, including
+/// steps of transactions that are later rolled back. Each call sets
+/// `include_params` afresh, so bound values are only kept when it is passed
+/// as true. Turning the log off keeps what was already recorded.
+///
+/// # Arguments
+/// * `capacity` - Entries to keep before the oldest are dropped; defaults to 1000
+/// * `include_params` - Record bound values instead of just their count
+#[tauri::command]
+pub fn set_query_log(
+    enabled: bool,
+    capacity: Option<usize>,
+    include_params: Option<bool>,
+    state: State<'_, DbState>,
+) -> Result<(), DbError> {
+    if capacity == Some(0) {
+        return Err(DbError::Invalid(
+            "Query log capacity must be at least 1".to_string(),
+        ));
+    }
+
+    let log = &state.query_log;
+    let mut buffer = log.buffer.lock().map_err(handle_poison_error)?;
+    if let Some(capacity) = capacity {
+        buffer.capacity = capacity;
+        let excess = buffer.entries.len().saturating_sub(capacity);
+        buffer.entries.drain(..excess);
+    }
+    buffer.include_params = include_params.unwrap_or(false);
+    log.enabled.store(enabled, Ordering::Relaxed);
+
+    log::info!("Query log {}", if enabled { "enabled" } else { "disabled" });
+    Ok(())
+}
+
+/// The most recent query log entries, oldest first
+///
+/// # Arguments
+/// * `limit` - Return at most this many of the newest entries; all when None
+#[tauri::command]
+pub fn get_query_log(
+    limit: Option<usize>,
+    state: State<'_, DbState>,
+) -> Result<Vec<QueryLogEntry>, DbError> {
+    let buffer = state.query_log.buffer.lock().map_err(handle_poison_error)?;
+    let skip = limit.map_or(0, |limit| buffer.entries.len().saturating_sub(limit));
+    Ok(buffer.entries.iter().skip(skip).cloned().collect())
+}
+
+/// Remove every entry from the query log
+#[tauri::command]
+pub fn clear_query_log(state: State<'_, DbState>) -> Result<(), DbError> {
+    state
+        .query_log
+        .buffer
+        .lock()
+        .map_err(handle_poison_error)?
+        .entries
+        .clear();
+    Ok(())
+}
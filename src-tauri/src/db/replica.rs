@@ -0,0 +1,117 @@
+//! Read replicas
+//!
+//! A database can name a second file, such as a copy synced from it
+//! periodically, that execute_transaction sends transactions made only of
+//! reads to, so heavy reporting queries don't compete with writes to the
+//! primary. Anything that writes still goes to the primary. If the replica
+//! can't be opened, or its connections fail, reads fall back to the primary.
+//!
+//! The replica is only as current as its last sync: a read on it may not
+//! see a write just committed to the primary, and two reads can disagree if
+//! a sync lands between them. A sync that replaces the replica's file, as
+//! backup_database with `overwrite` does, isn't seen by connections already
+//! open on the old file, so close the replica with close_connection after
+//! each sync; the next read opens the new file.
+
+use tauri::State;
+
+use super::{get_or_create_pool, handle_poison_error, is_in_memory, pool_key, DbError, DbState};
+
+/// The replica set for a database and its pool, or None to read from the database itself
+///
+/// A replica that fails to open is logged and skipped, so the read goes to
+/// the primary instead.
+pub(super) async fn replica_pool(
+    state: &DbState,
+    db_url: &str,
+) -> Result<Option<(String, sqlx::SqlitePool)>, DbError> {
+    let key = pool_key(state, db_url)?;
+    let replica_url = state
+        .read_replicas
+        .lock()
+        .map_err(handle_poison_error)?
+        .get(&key)
+        .cloned();
+    let Some(replica_url) = replica_url else {
+        return Ok(None);
+    };
+
+    match get_or_create_pool(state, &replica_url).await {
+        Ok(pool) => Ok(Some((replica_url, pool))),
+        Err(err) => {
+            log::warn!(
+                "Read replica {} of {} is unavailable, reading from the primary: {}",
+                replica_url,
+                db_url,
+                err
+            );
+            Ok(None)
+        }
+    }
+}
+
+/// Send read-only transactions on a database to a replica file
+///
+/// Transactions whose steps all read (SELECT, VALUES, or WITH queries that
+/// don't write) and that use the default deferred mode run on `replica_url`;
+/// everything else keeps using `db_url`. The replica is opened like any
+/// other database, so configure_pool can set it up first, for example
+/// `readOnly` to be sure nothing writes to it. Pass None to read from the
+/// primary again.
+///
+/// The replica lags the primary by however long its sync takes, so don't
+/// use it for reads that must see the caller's own writes.
+#[tauri::command]
+pub fn set_read_replica(
+    db_url: String,
+    replica_url: Option<String>,
+    state: State<'_, DbState>,
+) -> Result<(), DbError> {
+    let key = pool_key(&state, &db_url)?;
+    let replica_url = replica_url
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty());
+
+    if let Some(replica_url) = &replica_url {
+        if is_in_memory(replica_url) {
+            return Err(DbError::Invalid(
+                "A read replica must be a database file, not an in-memory database".to_string(),
+            ));
+        }
+        if pool_key(&state, replica_url)? == key {
+            return Err(DbError::Invalid(
+                "A database can't be its own read replica".to_string(),
+            ));
+        }
+    }
+
+    let mut replicas = state.read_replicas.lock().map_err(handle_poison_error)?;
+    match replica_url {
+        Some(replica_url) => {
+            log::info!("Reading {} from replica {}", db_url, replica_url);
+            replicas.insert(key, replica_url);
+        }
+        None => {
+            if replicas.remove(&key).is_some() {
+                log::info!("Reading {} from the database itself", db_url);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The read replica set for a database, if any
+#[tauri::command]
+pub fn get_read_replica(
+    db_url: String,
+    state: State<'_, DbState>,
+) -> Result<Option<String>, DbError> {
+    let key = pool_key(&state, &db_url)?;
+    Ok(state
+        .read_replicas
+        .lock()
+        .map_err(handle_poison_error)?
+        .get(&key)
+        .cloned())
+}
@@ -0,0 +1,276 @@
+//! Comparing the schemas of two databases
+//!
+//! Tables, their columns and indexes are read with the table_xinfo and
+//! index_list pragmas rather than by parsing the CREATE statements, so two
+//! definitions that only differ in spelling or whitespace compare equal.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use sqlx::pool::PoolConnection;
+use sqlx::Sqlite;
+use tauri::State;
+
+use super::{get_or_create_pool, sql, DbError, DbState};
+
+/// A column as PRAGMA table_xinfo describes it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColumnDef {
+    /// Declared type as written, or empty if none was given
+    pub declared_type: String,
+    pub not_null: bool,
+    /// The DEFAULT expression as written
+    pub default: Option<String>,
+    /// Position in the primary key, counting from 1; 0 if not part of it
+    pub primary_key: i64,
+    /// Whether the column is generated (or hidden, for virtual tables)
+    pub generated: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexDef {
+    pub table: String,
+    pub unique: bool,
+    /// Indexed columns in order; None for an expression
+    pub columns: Vec<Option<String>>,
+    /// The WHERE clause of a partial index, as in its CREATE statement
+    pub partial: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColumnRef {
+    pub table: String,
+    pub column: String,
+}
+
+/// Tables, columns and indexes found in only one of the databases
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaObjects {
+    pub tables: Vec<String>,
+    /// Columns of tables that exist in both databases
+    pub columns: Vec<ColumnRef>,
+    pub indexes: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColumnChange {
+    pub table: String,
+    pub column: String,
+    pub a: ColumnDef,
+    pub b: ColumnDef,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexChange {
+    pub name: String,
+    pub a: IndexDef,
+    pub b: IndexDef,
+}
+
+/// How the schema of database B differs from database A
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaDiff {
+    /// Whether the schemas have the same tables, columns and indexes
+    pub identical: bool,
+    pub only_in_a: SchemaObjects,
+    pub only_in_b: SchemaObjects,
+    /// Columns in both databases whose definitions differ
+    pub changed_columns: Vec<ColumnChange>,
+    /// Indexes in both databases that cover different columns or rows
+    pub changed_indexes: Vec<IndexChange>,
+}
+
+/// A database's tables with their columns, and its indexes, by name
+#[derive(Default)]
+struct Schema {
+    tables: BTreeMap<String, BTreeMap<String, ColumnDef>>,
+    indexes: BTreeMap<String, IndexDef>,
+}
+
+/// The WHERE clause of a CREATE INDEX statement, with whitespace collapsed
+fn partial_clause(sql: &str) -> Option<String> {
+    let clause = sql::index_where_clause(sql)?.split_whitespace();
+    Some(clause.collect::<Vec<_>>().join(" "))
+}
+
+async fn read_schema(conn: &mut PoolConnection<Sqlite>) -> Result<Schema, DbError> {
+    let read_error = |e| DbError::execute("Failed to read schema", e);
+    let mut schema = Schema::default();
+
+    // Shadow tables belong to a virtual table and are compared through it
+    let tables: Vec<String> = sqlx::query_scalar(
+        "SELECT s.name FROM sqlite_schema AS s \
+         LEFT JOIN pragma_table_list AS l ON l.schema = 'main' AND l.name = s.name \
+         WHERE s.type = 'table' AND s.name NOT LIKE 'sqlite\\_%' ESCAPE '\\' \
+         AND coalesce(l.type, '') != 'shadow'",
+    )
+    .fetch_all(&mut **conn)
+    .await
+    .map_err(read_error)?;
+
+    for table in tables {
+        let columns: Vec<(String, String, bool, Option<String>, i64, i64)> = sqlx::query_as(
+            "SELECT name, type, \"notnull\", dflt_value, pk, hidden FROM pragma_table_xinfo(?)",
+        )
+        .bind(&table)
+        .fetch_all(&mut **conn)
+        .await
+        .map_err(read_error)?;
+        let columns = columns
+            .into_iter()
+            .map(
+                |(name, declared_type, not_null, default, primary_key, hidden)| {
+                    let column = ColumnDef {
+                        declared_type,
+                        not_null,
+                        default,
+                        primary_key,
+                        generated: hidden != 0,
+                    };
+                    (name, column)
+                },
+            )
+            .collect();
+
+        let indexes: Vec<(String, bool, Option<String>)> = sqlx::query_as(
+            "SELECT l.name, l.\"unique\", s.sql FROM pragma_index_list(?) AS l \
+             LEFT JOIN sqlite_schema AS s ON s.type = 'index' AND s.name = l.name",
+        )
+        .bind(&table)
+        .fetch_all(&mut **conn)
+        .await
+        .map_err(read_error)?;
+        for (name, unique, sql) in indexes {
+            let index_columns: Vec<Option<String>> =
+                sqlx::query_scalar("SELECT name FROM pragma_index_info(?) ORDER BY seqno")
+                    .bind(&name)
+                    .fetch_all(&mut **conn)
+                    .await
+                    .map_err(read_error)?;
+            let index = IndexDef {
+                table: table.clone(),
+                unique,
+                columns: index_columns,
+                partial: sql.as_deref().and_then(partial_clause),
+            };
+            schema.indexes.insert(name, index);
+        }
+
+        schema.tables.insert(table, columns);
+    }
+
+    Ok(schema)
+}
+
+/// Names in `first` that `second` doesn't have
+fn missing<'a, V>(
+    first: &'a BTreeMap<String, V>,
+    second: &'a BTreeMap<String, V>,
+) -> impl Iterator<Item = &'a String> {
+    first.keys().filter(|name| !second.contains_key(*name))
+}
+
+fn compare(a: &Schema, b: &Schema) -> SchemaDiff {
+    let mut only_in_a = SchemaObjects::default();
+    let mut only_in_b = SchemaObjects::default();
+    let mut changed_columns = Vec::new();
+    let mut changed_indexes = Vec::new();
+
+    only_in_a.tables = missing(&a.tables, &b.tables).cloned().collect();
+    only_in_b.tables = missing(&b.tables, &a.tables).cloned().collect();
+
+    for (table, columns_a) in &a.tables {
+        let Some(columns_b) = b.tables.get(table) else {
+            continue;
+        };
+        let column_ref = |column: &String| ColumnRef {
+            table: table.clone(),
+            column: column.clone(),
+        };
+        only_in_a
+            .columns
+            .extend(missing(columns_a, columns_b).map(column_ref));
+        only_in_b
+            .columns
+            .extend(missing(columns_b, columns_a).map(column_ref));
+
+        for (column, def_a) in columns_a {
+            let Some(def_b) = columns_b.get(column) else {
+                continue;
+            };
+            let same = def_a
+                .declared_type
+                .eq_ignore_ascii_case(&def_b.declared_type)
+                && def_a.not_null == def_b.not_null
+                && def_a.default == def_b.default
+                && def_a.primary_key == def_b.primary_key
+                && def_a.generated == def_b.generated;
+            if !same {
+                changed_columns.push(ColumnChange {
+                    table: table.clone(),
+                    column: column.clone(),
+                    a: def_a.clone(),
+                    b: def_b.clone(),
+                });
+            }
+        }
+    }
+
+    only_in_a.indexes = missing(&a.indexes, &b.indexes).cloned().collect();
+    only_in_b.indexes = missing(&b.indexes, &a.indexes).cloned().collect();
+    for (name, index_a) in &a.indexes {
+        match b.indexes.get(name) {
+            Some(index_b) if index_a != index_b => changed_indexes.push(IndexChange {
+                name: name.clone(),
+                a: index_a.clone(),
+                b: index_b.clone(),
+            }),
+            _ => {}
+        }
+    }
+
+    let identical = [&only_in_a, &only_in_b].iter().all(|objects| {
+        objects.tables.is_empty() && objects.columns.is_empty() && objects.indexes.is_empty()
+    }) && changed_columns.is_empty()
+        && changed_indexes.is_empty();
+
+    SchemaDiff {
+        identical,
+        only_in_a,
+        only_in_b,
+        changed_columns,
+        changed_indexes,
+    }
+}
+
+/// Compare the schemas of two databases
+///
+/// Reports the tables, columns and indexes that only one database has, and
+/// the columns and indexes both have but define differently. Declared types
+/// are compared case-insensitively; column order, views and triggers are not
+/// compared. Only the main schema of each database is read, not attached ones.
+#[tauri::command]
+pub async fn schema_diff(
+    db_url_a: String,
+    db_url_b: String,
+    state: State<'_, DbState>,
+) -> Result<SchemaDiff, DbError> {
+    let mut schemas = Vec::with_capacity(2);
+    for db_url in [&db_url_a, &db_url_b] {
+        let pool = get_or_create_pool(&state, db_url).await?;
+        let mut conn = pool
+            .acquire()
+            .await
+            .map_err(|e| DbError::connect("Failed to acquire connection", e))?;
+        schemas.push(read_schema(&mut conn).await?);
+    }
+
+    Ok(compare(&schemas[0], &schemas[1]))
+}
@@ -0,0 +1,116 @@
+//! Statements run on every new connection of a pool
+//!
+//! Settings such as most PRAGMAs only apply to the connection they run on.
+//! Setup statements registered for a database URL run on each connection the
+//! pool opens, before it is used. The list is kept in DbState rather than in
+//! the pool, so it still applies after the pool is closed and opened again.
+
+use std::sync::{Arc, Mutex};
+
+use sqlx::{Executor, SqliteConnection};
+use tauri::State;
+use tokio::sync::oneshot;
+
+use super::{handle_poison_error, pool_key, read_only_key, DbError, DbState};
+
+/// The setup statements registered for a database URL
+pub(super) fn setup_statements(state: &DbState, db_url: &str) -> Result<Arc<[String]>, DbError> {
+    let setup = state.setup_statements.lock().map_err(handle_poison_error)?;
+    Ok(setup.get(db_url).cloned().unwrap_or_default())
+}
+
+/// Hands the first setup failure of a pool to the code opening it
+///
+/// A pool retries a connection whose setup fails until it times out, which
+/// would hide the statement's error, so create_pool waits for this alongside
+/// the pool and fails with the error instead.
+pub(super) struct SetupFailure(Mutex<Option<oneshot::Sender<DbError>>>);
+
+impl SetupFailure {
+    pub(super) fn new() -> (Self, oneshot::Receiver<DbError>) {
+        let (sender, receiver) = oneshot::channel();
+        (SetupFailure(Mutex::new(Some(sender))), receiver)
+    }
+
+    fn report(&self, error: DbError) {
+        let sender = self.0.lock().ok().and_then(|mut sender| sender.take());
+        if let Some(sender) = sender {
+            // Nobody is waiting once the pool has opened
+            let _ = sender.send(error);
+        }
+    }
+}
+
+/// Run setup statements on a newly opened connection, in order
+///
+/// The first statement that fails is reported to `failure` as well.
+pub(super) async fn run_setup(
+    conn: &mut SqliteConnection,
+    statements: &[String],
+    failure: &SetupFailure,
+) -> Result<(), sqlx::Error> {
+    for (index, statement) in statements.iter().enumerate() {
+        if let Err(e) = conn.execute(statement.as_str()).await {
+            let error = DbError::execute(&format!("Setup statement {} failed", index), e);
+            let message = error.to_string();
+            failure.report(error);
+            return Err(sqlx::Error::Configuration(message.into()));
+        }
+    }
+    Ok(())
+}
+
+/// Register statements to run on every connection opened for a database URL
+///
+/// Replaces any statements registered before; an empty list removes them.
+/// They also run on the read-only pool query_rows_readonly uses, where a
+/// statement that writes will fail. Like configure_pool this must be called
+/// before the database is opened. If a statement fails, opening the database
+/// fails with its error.
+#[tauri::command]
+pub fn set_connection_setup(
+    db_url: String,
+    statements: Vec<String>,
+    state: State<'_, DbState>,
+) -> Result<(), DbError> {
+    if let Some(index) = statements.iter().position(|sql| sql.trim().is_empty()) {
+        return Err(DbError::Invalid(format!(
+            "Setup statement {} is empty",
+            index
+        )));
+    }
+
+    let key = pool_key(&state, &db_url)?;
+    let already_open = {
+        let connections_guard = state.connections.lock().map_err(handle_poison_error)?;
+        connections_guard.contains_key(&key) || connections_guard.contains_key(&read_only_key(&key))
+    };
+    if already_open {
+        return Err(DbError::Invalid(
+            "Database is already open; close it before changing its setup statements".to_string(),
+        ));
+    }
+
+    let mut setup = state.setup_statements.lock().map_err(handle_poison_error)?;
+    if statements.is_empty() {
+        setup.remove(&key);
+    } else {
+        log::info!(
+            "Registered {} setup statements for {}",
+            statements.len(),
+            db_url
+        );
+        setup.insert(key, statements.into());
+    }
+
+    Ok(())
+}
+
+/// The setup statements registered for a database URL
+#[tauri::command]
+pub fn get_connection_setup(
+    db_url: String,
+    state: State<'_, DbState>,
+) -> Result<Vec<String>, DbError> {
+    Ok(setup_statements(&state, &pool_key(&state, &db_url)?)?.to_vec())
+}
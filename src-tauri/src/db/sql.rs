@@ -0,0 +1,558 @@
+//! Lexical helpers for SQL text
+//!
+//! These understand just enough SQLite syntax (string literals, quoted
+//! identifiers, comments and trigger bodies) to find placeholders and
+//! statement boundaries without being fooled by text that only looks like SQL.
+
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TokenKind {
+    /// Whitespace or a comment
+    Space,
+    /// A string literal or quoted identifier
+    Quoted,
+    /// A run of identifier characters (keywords, names, numbers)
+    Word,
+    Semicolon,
+    /// Any other single character
+    Punct,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    range: Range<usize>,
+}
+
+fn is_identifier_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b >= 0x80
+}
+
+/// Split SQL text into tokens covering every byte of the input
+fn lex(sql: &str) -> Vec<Token> {
+    let bytes = sql.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let (kind, end) = match bytes[i] {
+            quote @ (b'\'' | b'"' | b'`') => (TokenKind::Quoted, skip_quoted(bytes, i, quote)),
+            b'[' => (
+                TokenKind::Quoted,
+                find_from(bytes, i + 1, b"]").map_or(bytes.len(), |end| end + 1),
+            ),
+            b'-' if bytes.get(i + 1) == Some(&b'-') => (
+                TokenKind::Space,
+                find_from(bytes, i + 2, b"\n").map_or(bytes.len(), |end| end + 1),
+            ),
+            b'/' if bytes.get(i + 1) == Some(&b'*') => (
+                TokenKind::Space,
+                find_from(bytes, i + 2, b"*/").map_or(bytes.len(), |end| end + 2),
+            ),
+            b if b.is_ascii_whitespace() => {
+                let mut end = i + 1;
+                while end < bytes.len() && bytes[end].is_ascii_whitespace() {
+                    end += 1;
+                }
+                (TokenKind::Space, end)
+            }
+            b if is_identifier_byte(b) => {
+                let mut end = i + 1;
+                while end < bytes.len() && is_identifier_byte(bytes[end]) {
+                    end += 1;
+                }
+                (TokenKind::Word, end)
+            }
+            b';' => (TokenKind::Semicolon, i + 1),
+            _ => (TokenKind::Punct, i + 1),
+        };
+
+        tokens.push(Token {
+            kind,
+            range: i..end,
+        });
+        i = end;
+    }
+
+    tokens
+}
+
+/// Index just past the closing quote of a quoted token starting at `open`
+///
+/// A doubled quote character inside the token is an escaped quote.
+fn skip_quoted(bytes: &[u8], open: usize, quote: u8) -> usize {
+    let mut i = open + 1;
+    while i < bytes.len() {
+        if bytes[i] == quote {
+            if bytes.get(i + 1) == Some(&quote) {
+                i += 2;
+                continue;
+            }
+            return i + 1;
+        }
+        i += 1;
+    }
+    bytes.len()
+}
+
+fn find_from(bytes: &[u8], from: usize, needle: &[u8]) -> Option<usize> {
+    bytes
+        .get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|offset| from + offset)
+}
+
+// States of sqlite3_complete()
+const START: usize = 1;
+const NORMAL: usize = 2;
+const EXPLAIN: usize = 3;
+const CREATE: usize = 4;
+const TRIGGER: usize = 5;
+const SEMI: usize = 6;
+const END: usize = 7;
+
+// Token classes of sqlite3_complete(), used as the column index
+const T_SEMI: usize = 0;
+const T_WS: usize = 1;
+const T_OTHER: usize = 2;
+const T_EXPLAIN: usize = 3;
+const T_CREATE: usize = 4;
+const T_TEMP: usize = 5;
+const T_TRIGGER: usize = 6;
+const T_END: usize = 7;
+
+#[rustfmt::skip]
+const TRANSITIONS: [[usize; 8]; 8] = [
+    //  SEMI   WS       OTHER    EXPLAIN  CREATE  TEMP    TRIGGER  END
+    [START, 0,       NORMAL,  EXPLAIN, CREATE, NORMAL, NORMAL,  NORMAL], // INVALID
+    [START, START,   NORMAL,  EXPLAIN, CREATE, NORMAL, NORMAL,  NORMAL], // START
+    [START, NORMAL,  NORMAL,  NORMAL,  NORMAL, NORMAL, NORMAL,  NORMAL], // NORMAL
+    [START, EXPLAIN, EXPLAIN, NORMAL,  CREATE, NORMAL, NORMAL,  NORMAL], // EXPLAIN
+    [START, CREATE,  NORMAL,  NORMAL,  NORMAL, CREATE, TRIGGER, NORMAL], // CREATE
+    [SEMI,  TRIGGER, TRIGGER, TRIGGER, TRIGGER, TRIGGER, TRIGGER, TRIGGER], // TRIGGER
+    [SEMI,  SEMI,    TRIGGER, TRIGGER, TRIGGER, TRIGGER, TRIGGER, END], // SEMI
+    [START, END,     TRIGGER, TRIGGER, TRIGGER, TRIGGER, TRIGGER, TRIGGER], // END
+];
+
+/// The sqlite3_complete() class of a token
+fn token_class(sql: &str, token: &Token) -> usize {
+    match token.kind {
+        TokenKind::Space => T_WS,
+        TokenKind::Semicolon => T_SEMI,
+        TokenKind::Quoted | TokenKind::Punct => T_OTHER,
+        TokenKind::Word => {
+            let word = &sql[token.range.clone()];
+            if word.eq_ignore_ascii_case("EXPLAIN") {
+                T_EXPLAIN
+            } else if word.eq_ignore_ascii_case("CREATE") {
+                T_CREATE
+            } else if word.eq_ignore_ascii_case("TEMP") || word.eq_ignore_ascii_case("TEMPORARY") {
+                T_TEMP
+            } else if word.eq_ignore_ascii_case("TRIGGER") {
+                T_TRIGGER
+            } else if word.eq_ignore_ascii_case("END") {
+                T_END
+            } else {
+                T_OTHER
+            }
+        }
+    }
+}
+
+/// Where split_statements is in a script: the state of sqlite3_complete()
+/// and the statement read so far
+#[derive(Default)]
+struct SplitState {
+    state: usize,
+    /// Byte range from the first to the last meaningful token of the current statement
+    current: Option<Range<usize>>,
+}
+
+impl SplitState {
+    /// Advance past one token, returning the range of the statement it ends, if any
+    fn advance(&mut self, sql: &str, token: &Token) -> Option<Range<usize>> {
+        let class = token_class(sql, token);
+        self.state = TRANSITIONS[self.state][class];
+
+        if self.state == START && class == T_SEMI {
+            return self.current.take();
+        }
+
+        if class != T_WS {
+            self.current = Some(match self.current.take() {
+                Some(range) => range.start..token.range.end,
+                None => token.range.clone(),
+            });
+        }
+        None
+    }
+}
+
+/// Split a script into its individual statements
+///
+/// Statement boundaries follow the same state machine as SQLite's
+/// `sqlite3_complete()`, so semicolons inside literals, comments and
+/// `CREATE TRIGGER ... BEGIN ... END` bodies do not end a statement.
+/// Returned statements exclude the terminating semicolon and any leading or
+/// trailing whitespace and comments; empty statements are skipped. Trailing
+/// text without a final semicolon is returned as the last statement.
+pub fn split_statements(sql: &str) -> Vec<&str> {
+    let mut split = SplitState::default();
+    let mut statements: Vec<&str> = lex(sql)
+        .iter()
+        .filter_map(|token| split.advance(sql, token))
+        .map(|range| &sql[range])
+        .collect();
+
+    if let Some(range) = split.current {
+        statements.push(&sql[range]);
+    }
+
+    statements
+}
+
+/// Splits a script read a piece at a time, like split_statements
+///
+/// Each piece is lexed once rather than the whole script again, so a long
+/// statement spread over many pieces costs no more than reading it. Only the
+/// last token seen is lexed again, since the next piece may continue it; a
+/// token that stays unfinished is retried once the text after it has doubled.
+#[derive(Default)]
+pub struct ScriptSplitter {
+    /// Text from the start of the current statement on
+    buffer: String,
+    split: SplitState,
+    /// Bytes of `buffer` lexed so far; always a token boundary
+    scanned: usize,
+    /// Length of the unfinished token at `scanned` when it was last lexed
+    held_back: usize,
+}
+
+impl ScriptSplitter {
+    /// Add the next piece of the script
+    pub fn push_str(&mut self, text: &str) {
+        self.buffer.push_str(text);
+    }
+
+    /// Take the statements the text added so far completes
+    ///
+    /// With `at_end` set the rest of the buffer is taken too, as the last
+    /// statement if it isn't empty.
+    pub fn take_statements(&mut self, at_end: bool) -> Vec<String> {
+        let unscanned = self.buffer.len() - self.scanned;
+        if !at_end && unscanned < 2 * self.held_back {
+            return Vec::new();
+        }
+
+        let mut tokens = lex(&self.buffer[self.scanned..]);
+        for token in &mut tokens {
+            token.range = token.range.start + self.scanned..token.range.end + self.scanned;
+        }
+        // The next piece may continue the last token
+        let held = if at_end { None } else { tokens.pop() };
+
+        let mut statements = Vec::new();
+        for token in &tokens {
+            if let Some(range) = self.split.advance(&self.buffer, token) {
+                statements.push(self.buffer[range].to_string());
+            }
+        }
+        match held {
+            Some(token) => {
+                self.scanned = token.range.start;
+                self.held_back = token.range.len();
+            }
+            None => {
+                self.scanned = self.buffer.len();
+                self.held_back = 0;
+            }
+        }
+        if at_end {
+            if let Some(range) = self.split.current.take() {
+                statements.push(self.buffer[range].to_string());
+            }
+        }
+
+        // Drop the text before the current statement
+        let keep_from = self
+            .split
+            .current
+            .as_ref()
+            .map_or(self.scanned, |range| range.start);
+        self.buffer.drain(..keep_from);
+        self.scanned -= keep_from;
+        if let Some(range) = &mut self.split.current {
+            *range = range.start - keep_from..range.end - keep_from;
+        }
+
+        statements
+    }
+}
+
+/// Rewrite `:name`, `@name` and `$name` placeholders as numbered `?NNN` placeholders
+///
+/// Returns the rewritten SQL and the distinct parameter names in the order
+/// their numbers were assigned, so the caller can bind values positionally.
+/// A name used more than once gets the same number each time.
+pub fn rewrite_named_params(sql: &str) -> Result<(String, Vec<String>), String> {
+    let tokens = lex(sql);
+    let mut rewritten = String::with_capacity(sql.len());
+    let mut names: Vec<String> = Vec::new();
+    let mut copied_to = 0;
+
+    for (i, token) in tokens.iter().enumerate() {
+        if token.kind != TokenKind::Punct {
+            continue;
+        }
+
+        match &sql[token.range.clone()] {
+            "?" => {
+                return Err(
+                    "Positional '?' placeholders cannot be mixed with named parameters".to_string(),
+                );
+            }
+            ":" | "@" | "$" => {
+                let Some(name_token) = tokens
+                    .get(i + 1)
+                    .filter(|next| next.kind == TokenKind::Word)
+                else {
+                    continue;
+                };
+                let name = &sql[name_token.range.clone()];
+
+                let number = match names.iter().position(|existing| existing == name) {
+                    Some(index) => index + 1,
+                    None => {
+                        names.push(name.to_string());
+                        names.len()
+                    }
+                };
+
+                rewritten.push_str(&sql[copied_to..token.range.start]);
+                rewritten.push_str(&format!("?{}", number));
+                copied_to = name_token.range.end;
+            }
+            _ => {}
+        }
+    }
+
+    rewritten.push_str(&sql[copied_to..]);
+
+    Ok((rewritten, names))
+}
+
+/// The unquoted text of a name token, if it is one
+fn name_text(sql: &str, token: &Token) -> Option<String> {
+    let text = &sql[token.range.clone()];
+    match token.kind {
+        TokenKind::Word => Some(text.to_string()),
+        TokenKind::Quoted if text.len() >= 2 => {
+            let inner = &text[1..text.len() - 1];
+            Some(match text.as_bytes()[0] {
+                b'[' => inner.to_string(),
+                quote => {
+                    let quote = (quote as char).to_string();
+                    inner.replace(&quote.repeat(2), &quote)
+                }
+            })
+        }
+        _ => None,
+    }
+}
+
+/// The keyword that says what a single statement does, uppercased (e.g. "INSERT")
+///
+/// Leading whitespace and comments are skipped. For a statement starting
+/// with a `WITH` clause this is the keyword after its common table
+/// expressions, such as UPDATE in `WITH ids AS (...) UPDATE ...`.
+pub fn statement_keyword(sql: &str) -> Option<String> {
+    let mut tokens = lex(sql)
+        .into_iter()
+        .filter(|token| token.kind != TokenKind::Space);
+    let first = tokens
+        .next()
+        .filter(|token| token.kind == TokenKind::Word)?;
+    let keyword = sql[first.range].to_ascii_uppercase();
+    if keyword != "WITH" {
+        return Some(keyword);
+    }
+
+    let mut depth = 0usize;
+    tokens.find_map(|token| {
+        let text = &sql[token.range];
+        match (token.kind, text) {
+            (TokenKind::Punct, "(") => depth += 1,
+            (TokenKind::Punct, ")") => depth = depth.saturating_sub(1),
+            (TokenKind::Word, _) if depth == 0 => {
+                let word = text.to_ascii_uppercase();
+                if matches!(
+                    word.as_str(),
+                    "SELECT" | "VALUES" | "INSERT" | "REPLACE" | "UPDATE" | "DELETE"
+                ) {
+                    return Some(word);
+                }
+            }
+            _ => {}
+        }
+        None
+    })
+}
+
+/// The table a single statement writes to or changes the schema of, if any
+///
+/// Recognizes INSERT, REPLACE, UPDATE and DELETE (including after a `WITH`
+/// clause) and CREATE, DROP and ALTER TABLE. The name is returned as written,
+/// unquoted and with its schema prefix if it has one. Tables changed
+/// indirectly, by triggers or foreign key actions, are not reported.
+pub fn written_table(sql: &str) -> Option<String> {
+    let tokens: Vec<Token> = lex(sql)
+        .into_iter()
+        .filter(|token| token.kind != TokenKind::Space)
+        .collect();
+    let is_word = |i: usize, word: &str| {
+        tokens.get(i).is_some_and(|token| {
+            token.kind == TokenKind::Word && sql[token.range.clone()].eq_ignore_ascii_case(word)
+        })
+    };
+    let is_punct = |i: usize, punct: &str| {
+        tokens
+            .get(i)
+            .is_some_and(|token| &sql[token.range.clone()] == punct)
+    };
+
+    let mut i = 0;
+    if is_word(0, "WITH") {
+        // Skip the common table expressions to the statement they belong to
+        let mut depth = 0usize;
+        i = (1..tokens.len()).find(|&i| {
+            if is_punct(i, "(") {
+                depth += 1;
+            } else if is_punct(i, ")") {
+                depth = depth.saturating_sub(1);
+            }
+            depth == 0
+                && ["INSERT", "REPLACE", "UPDATE", "DELETE"]
+                    .iter()
+                    .any(|w| is_word(i, w))
+        })?;
+    }
+
+    if is_word(i, "INSERT") || is_word(i, "REPLACE") {
+        i = (i..tokens.len()).find(|&i| is_word(i, "INTO"))? + 1;
+    } else if is_word(i, "UPDATE") {
+        i += 1;
+        if is_word(i, "OR") {
+            i += 2;
+        }
+    } else if is_word(i, "DELETE") && is_word(i + 1, "FROM") {
+        i += 2;
+    } else if is_word(i, "CREATE") {
+        i += 1;
+        while is_word(i, "TEMP") || is_word(i, "TEMPORARY") || is_word(i, "VIRTUAL") {
+            i += 1;
+        }
+        if !is_word(i, "TABLE") {
+            return None;
+        }
+        i += 1;
+        if is_word(i, "IF") && is_word(i + 1, "NOT") && is_word(i + 2, "EXISTS") {
+            i += 3;
+        }
+    } else if (is_word(i, "DROP") || is_word(i, "ALTER")) && is_word(i + 1, "TABLE") {
+        let alter = is_word(i, "ALTER");
+        i += 2;
+        if !alter && is_word(i, "IF") && is_word(i + 1, "EXISTS") {
+            i += 2;
+        }
+    } else {
+        return None;
+    }
+
+    let name = name_text(sql, tokens.get(i)?)?;
+    if is_punct(i + 1, ".") {
+        let table = name_text(sql, tokens.get(i + 2)?)?;
+        return Some(format!("{}.{}", name, table));
+    }
+    Some(name)
+}
+
+/// The condition of a partial index: the text after its top-level WHERE
+///
+/// `sql` is a CREATE INDEX statement. Only a WHERE after the parenthesized
+/// column list counts, so one inside a quoted name, a literal, a comment or
+/// an indexed expression is skipped.
+pub fn index_where_clause(sql: &str) -> Option<&str> {
+    let mut depth = 0usize;
+    let mut after_columns = false;
+    for token in lex(sql) {
+        let text = &sql[token.range.clone()];
+        match (token.kind, text) {
+            (TokenKind::Punct, "(") => depth += 1,
+            (TokenKind::Punct, ")") => {
+                depth = depth.saturating_sub(1);
+                after_columns |= depth == 0;
+            }
+            (TokenKind::Word, _)
+                if after_columns && depth == 0 && text.eq_ignore_ascii_case("WHERE") =>
+            {
+                return Some(sql[token.range.end..].trim());
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Quote an identifier (table or column name) for use in SQL text
+pub fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script_splitter_matches_split_statements_for_any_piece_size() {
+        let script = "CREATE TABLE t (x TEXT); -- a; comment\n\
+            INSERT INTO t VALUES ('semi;colon'), (\"é;\");\n\
+            /* block; */ CREATE TRIGGER tr AFTER INSERT ON t BEGIN\n\
+            UPDATE t SET x = 'ü' WHERE x = ';'; END;\n\
+            ;; SELECT [odd;name] FROM t - -1; VALUES (X'00ff')";
+        let expected = split_statements(script);
+
+        for size in 1..=script.len() {
+            let mut splitter = ScriptSplitter::default();
+            let mut statements = Vec::new();
+            let mut rest = script;
+            while !rest.is_empty() {
+                let mut end = size.min(rest.len());
+                while !rest.is_char_boundary(end) {
+                    end += 1;
+                }
+                splitter.push_str(&rest[..end]);
+                rest = &rest[end..];
+                statements.extend(splitter.take_statements(false));
+            }
+            statements.extend(splitter.take_statements(true));
+            assert_eq!(statements, expected, "pieces of {} bytes", size);
+        }
+    }
+
+    #[test]
+    fn index_where_clause_is_the_top_level_where() {
+        assert_eq!(
+            index_where_clause("CREATE INDEX i ON t (a)\n\tWHERE a IS NOT NULL"),
+            Some("a IS NOT NULL")
+        );
+        assert_eq!(
+            index_where_clause(
+                "CREATE INDEX \"where (\" ON t (\"a where\", (b || ' WHERE ')) /* WHERE */ WHERE b > 0"
+            ),
+            Some("b > 0")
+        );
+        assert_eq!(index_where_clause("CREATE INDEX i ON t (a)"), None);
+    }
+}
@@ -0,0 +1,207 @@
+//! Database file size and compacting with VACUUM
+//!
+//! database_stats reports how much space a database uses and how much of it
+//! is free pages that VACUUM would reclaim.
+//!
+//! VACUUM cannot run inside a transaction, so it has its own command rather
+//! than going through execute_transaction. It rebuilds the whole file and
+//! needs the write lock, so while another pooled connection is writing (or a
+//! cursor is still reading, outside WAL mode) it fails with SQLITE_BUSY; it
+//! is then retried with backoff like a busy transaction.
+//!
+//! wal_checkpoint copies the WAL file back into the database on demand, for
+//! example after a large import or before a backup. It is a PRAGMA that can't
+//! run in a transaction either.
+
+use serde::{Deserialize, Serialize};
+use sqlx::{Executor, Row};
+use tauri::State;
+
+use super::{get_or_create_pool, run_with_retries, DbError, DbState, DEFAULT_BUSY_RETRIES};
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VacuumResult {
+    /// Size of the main database file on disk before compacting, in bytes;
+    /// None for in-memory and temporary databases
+    pub size_before: Option<u64>,
+    pub size_after: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DatabaseStats {
+    /// Size of the main database file on disk; None for in-memory and temporary databases
+    pub file_bytes: Option<u64>,
+    pub page_count: i64,
+    pub page_size: i64,
+    /// Unused pages that VACUUM would remove
+    pub freelist_count: i64,
+}
+
+/// How much work a WAL checkpoint does, as in `PRAGMA wal_checkpoint(mode)`
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckpointMode {
+    /// Copy as much as possible without waiting on readers or writers
+    Passive,
+    /// Wait for writers, then copy the whole log
+    Full,
+    /// Like full, then wait for readers so the next writer starts the log over
+    Restart,
+    /// Like restart, and also truncate the WAL file to zero bytes
+    Truncate,
+}
+
+impl CheckpointMode {
+    fn sql(self) -> &'static str {
+        match self {
+            CheckpointMode::Passive => "PRAGMA wal_checkpoint(PASSIVE)",
+            CheckpointMode::Full => "PRAGMA wal_checkpoint(FULL)",
+            CheckpointMode::Restart => "PRAGMA wal_checkpoint(RESTART)",
+            CheckpointMode::Truncate => "PRAGMA wal_checkpoint(TRUNCATE)",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckpointResult {
+    /// A full, restart or truncate checkpoint couldn't finish because other
+    /// connections kept the database busy
+    pub busy: bool,
+    /// Frames in the WAL file; None when the database isn't in WAL mode
+    pub log_frames: Option<i64>,
+    /// Frames copied back into the database, including by earlier checkpoints;
+    /// None when the database isn't in WAL mode
+    pub checkpointed_frames: Option<i64>,
+}
+
+/// Report the size of a database and how much of it is free space
+///
+/// The file size doesn't include a WAL file next to it, which holds recent
+/// writes until they are checkpointed.
+#[tauri::command]
+pub async fn database_stats(
+    db_url: String,
+    state: State<'_, DbState>,
+) -> Result<DatabaseStats, DbError> {
+    let pool = get_or_create_pool(&state, &db_url).await?;
+    let read = |e| DbError::execute("Failed to read database stats", e);
+
+    let row = sqlx::query(
+        "SELECT page_count, page_size, freelist_count, \
+         (SELECT file FROM pragma_database_list WHERE name = 'main') AS file \
+         FROM pragma_page_count(), pragma_page_size(), pragma_freelist_count()",
+    )
+    .fetch_one(&pool)
+    .await
+    .map_err(read)?;
+
+    Ok(DatabaseStats {
+        file_bytes: file_size(row.try_get("file").map_err(read)?).await?,
+        page_count: row.try_get("page_count").map_err(read)?,
+        page_size: row.try_get("page_size").map_err(read)?,
+        freelist_count: row.try_get("freelist_count").map_err(read)?,
+    })
+}
+
+/// Size of the database file at `file` on disk; None when it has no file
+async fn file_size(file: Option<String>) -> Result<Option<u64>, DbError> {
+    match file.filter(|file| !file.is_empty()) {
+        Some(file) => tokio::fs::metadata(&file)
+            .await
+            .map(|metadata| Some(metadata.len()))
+            .map_err(|e| DbError::Invalid(format!("Failed to read {}: {}", file, e))),
+        None => Ok(None),
+    }
+}
+
+/// Size of the main database file of the database a pool opens
+async fn database_file_size(pool: &sqlx::SqlitePool) -> Result<Option<u64>, DbError> {
+    let file: Option<String> =
+        sqlx::query_scalar("SELECT file FROM pragma_database_list WHERE name = 'main'")
+            .fetch_one(pool)
+            .await
+            .map_err(|e| DbError::execute("Failed to read database size", e))?;
+    file_size(file).await
+}
+
+/// Rebuild a database file to reclaim the space left by deleted rows
+///
+/// Returns the size of the file on disk before and after so the UI can
+/// show how much was reclaimed. In WAL mode the log is checkpointed
+/// afterwards so the file on disk actually shrinks; a reader that is still
+/// open can delay that until its next checkpoint.
+#[tauri::command]
+pub async fn vacuum_database(
+    db_url: String,
+    state: State<'_, DbState>,
+) -> Result<VacuumResult, DbError> {
+    let pool = get_or_create_pool(&state, &db_url).await?;
+    let size_before = database_file_size(&pool).await?;
+
+    let vacuum_pool = &pool;
+    run_with_retries(DEFAULT_BUSY_RETRIES, move || async move {
+        vacuum_pool
+            .execute("VACUUM")
+            .await
+            .map_err(|e| DbError::execute("Failed to vacuum database", e))
+    })
+    .await?;
+
+    // Outside WAL mode this does nothing
+    if let Err(e) = pool.execute("PRAGMA wal_checkpoint(TRUNCATE)").await {
+        log::warn!("Failed to checkpoint after VACUUM: {}", e);
+    }
+
+    let size_after = database_file_size(&pool).await?;
+
+    log::info!(
+        "Vacuumed {}: {:?} bytes before, {:?} after",
+        db_url,
+        size_before,
+        size_after
+    );
+
+    Ok(VacuumResult {
+        size_before,
+        size_after,
+    })
+}
+
+/// Checkpoint a WAL database, copying its log back into the database file
+///
+/// Until a checkpoint runs, the log keeps growing and the database file
+/// itself lacks the latest writes, which matters for backups and tools that
+/// copy the file. A database that isn't in WAL mode is left as it is and
+/// reports no frames.
+#[tauri::command]
+pub async fn wal_checkpoint(
+    db_url: String,
+    mode: CheckpointMode,
+    state: State<'_, DbState>,
+) -> Result<CheckpointResult, DbError> {
+    let pool = get_or_create_pool(&state, &db_url).await?;
+    let (busy, log_frames, checkpointed_frames): (bool, i64, i64) = sqlx::query_as(mode.sql())
+        .fetch_one(&pool)
+        .await
+        .map_err(|e| DbError::execute("Failed to checkpoint database", e))?;
+
+    // SQLite reports -1 for both counts outside WAL mode
+    let frames = |count: i64| (count >= 0).then_some(count);
+    log::info!(
+        "Checkpointed {} ({:?}): {} of {} frames{}",
+        db_url,
+        mode,
+        checkpointed_frames,
+        log_frames,
+        if busy { ", busy" } else { "" }
+    );
+
+    Ok(CheckpointResult {
+        busy,
+        log_frames: frames(log_frames),
+        checkpointed_frames: frames(checkpointed_frames),
+    })
+}
@@ -0,0 +1,152 @@
+//! `invariant://` links
+//!
+//! tauri-plugin-deep-link registers the scheme with the OS when the app is
+//! installed. A link that launches the app is read at startup; one opened while
+//! the app runs reaches it through the plugin, or on Windows and Linux through
+//! the second instance that single-instance redirects here. Either way each
+//! link is parsed and sent to the frontend as a `deep-link` event.
+//!
+//! A link can arrive before the frontend listens for it, so links are queued
+//! until the frontend takes them with take_pending_deep_links; after that they
+//! are emitted as they come.
+//!
+//! Links come from outside the app, so the frontend should confirm before
+//! acting on one.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State, Url};
+use tauri_plugin_deep_link::DeepLinkExt;
+
+use crate::window;
+
+/// Scheme registered in the deep-link section of tauri.conf.json
+const SCHEME: &str = "invariant";
+const DEEP_LINK_EVENT: &str = "deep-link";
+
+/// What a link asks the app to do
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DeepLinkAction {
+    /// Open the database at the `db` parameter
+    Open,
+}
+
+/// Payload of `deep-link` events
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeepLink {
+    pub url: String,
+    pub action: DeepLinkAction,
+    /// Query parameters, decoded; a repeated parameter keeps its last value
+    pub params: BTreeMap<String, String>,
+}
+
+/// Links received before the frontend was ready, or None once it is
+pub struct PendingDeepLinks(Mutex<Option<Vec<DeepLink>>>);
+
+impl Default for PendingDeepLinks {
+    fn default() -> Self {
+        PendingDeepLinks(Mutex::new(Some(Vec::new())))
+    }
+}
+
+/// Parse an `invariant://<action>?<params>` link
+fn parse(url: &Url) -> Result<DeepLink, String> {
+    if url.scheme() != SCHEME {
+        return Err(format!("unsupported scheme {}", url.scheme()));
+    }
+
+    let params: BTreeMap<String, String> = url.query_pairs().into_owned().collect();
+    let action = match url.host_str().unwrap_or_default() {
+        "open" => {
+            if !params.get("db").is_some_and(|db| !db.trim().is_empty()) {
+                return Err("open links need a db parameter".to_string());
+            }
+            DeepLinkAction::Open
+        }
+        "" => return Err("missing action".to_string()),
+        action => return Err(format!("unknown action {}", action)),
+    };
+
+    Ok(DeepLink {
+        url: url.to_string(),
+        action,
+        params,
+    })
+}
+
+/// Parse links and pass them on to the frontend, skipping any that are invalid
+fn handle(app: &AppHandle, urls: Vec<Url>) {
+    let links: Vec<DeepLink> = urls
+        .iter()
+        .filter_map(|url| match parse(url) {
+            Ok(link) => Some(link),
+            Err(reason) => {
+                log::warn!("Ignoring deep link {}: {}", url, reason);
+                None
+            }
+        })
+        .collect();
+    if links.is_empty() {
+        return;
+    }
+
+    window::focus_main(app);
+
+    let pending = app.state::<PendingDeepLinks>();
+    let Ok(mut pending) = pending.0.lock() else {
+        return;
+    };
+    match pending.as_mut() {
+        Some(queue) => {
+            log::info!(
+                "Queued {} deep links until the frontend is ready",
+                links.len()
+            );
+            queue.extend(links);
+        }
+        None => {
+            for link in links {
+                log::info!("Opening deep link {}", link.url);
+                let _ = app.emit(DEEP_LINK_EVENT, link);
+            }
+        }
+    }
+}
+
+/// Handle the link the app was launched with and listen for later ones
+pub fn setup(app: &AppHandle) {
+    // Installers register the scheme; a development build has to do it itself
+    #[cfg(all(debug_assertions, any(windows, target_os = "linux")))]
+    if let Err(err) = app.deep_link().register_all() {
+        log::warn!("Failed to register the {} scheme: {}", SCHEME, err);
+    }
+
+    let app_handle = app.clone();
+    app.deep_link()
+        .on_open_url(move |event| handle(&app_handle, event.urls()));
+
+    match app.deep_link().get_current() {
+        Ok(Some(urls)) => handle(app, urls),
+        Ok(None) => {}
+        Err(err) => log::warn!("Failed to read the launch deep link: {}", err),
+    }
+}
+
+/// Links that arrived before the frontend was listening
+///
+/// Call once after listening for `deep-link` events; later links are only
+/// emitted as events.
+#[tauri::command]
+pub fn take_pending_deep_links(
+    state: State<'_, PendingDeepLinks>,
+) -> Result<Vec<DeepLink>, String> {
+    let mut pending = state
+        .0
+        .lock()
+        .map_err(|_| "Deep link queue is unavailable".to_string())?;
+    Ok(pending.take().unwrap_or_default())
+}
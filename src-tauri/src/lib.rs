@@ -1,62 +1,264 @@
+mod config;
 mod db;
 #[cfg(desktop)]
+mod deep_link;
+mod logging;
+mod notifications;
+#[cfg(desktop)]
+mod shortcuts;
+#[cfg(desktop)]
+mod tray;
+#[cfg(desktop)]
 mod updater;
+#[cfg(desktop)]
+mod window;
 
 use tauri::Manager;
+#[cfg(desktop)]
+use tauri::Emitter;
+
+/// Arguments of a launch that was redirected to the running instance
+#[cfg(desktop)]
+#[derive(Clone, serde::Serialize)]
+struct SecondInstance {
+    args: Vec<String>,
+    cwd: String,
+}
+
+/// Whether this build can check for and install updates
+///
+/// The updater commands are only registered on desktop, so the frontend
+/// checks this before showing any update UI.
+#[tauri::command]
+fn supports_updates() -> bool {
+    cfg!(desktop)
+}
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     let mut builder = tauri::Builder::default()
         .manage(db::DbState {
             connections: std::sync::Mutex::new(std::collections::HashMap::new()),
-        });
+            attachments: std::sync::Mutex::new(std::collections::HashMap::new()),
+            setup_statements: std::sync::Mutex::new(std::collections::HashMap::new()),
+            cancellations: std::sync::Mutex::new(std::collections::HashMap::new()),
+            query_log: db::query_log::QueryLog::default(),
+            pool_keys: std::sync::Mutex::new(std::collections::HashMap::new()),
+            read_replicas: std::sync::Mutex::new(std::collections::HashMap::new()),
+            shared_pools: std::sync::Mutex::new(std::collections::HashSet::new()),
+        })
+        .manage(db::cursor::CursorState::default());
+
+    // Registered first so a second launch exits before any other plugin starts.
+    // Its arguments go to the running instance as a "second-instance" event,
+    // after any deep link among them is handed to the deep-link plugin.
+    #[cfg(desktop)]
+    {
+        builder = builder.plugin(tauri_plugin_single_instance::init(|app, args, cwd| {
+            log::info!("Second instance launched with {:?}; focusing this one", args);
+            window::focus_main(app);
+            let _ = app.emit("second-instance", SecondInstance { args, cwd });
+        }));
+        builder = builder
+            .plugin(tauri_plugin_deep_link::init())
+            .manage(deep_link::PendingDeepLinks::default());
+    }
 
     #[cfg(desktop)]
     {
-        builder = builder.manage(updater::PendingUpdate(std::sync::Mutex::new(None)));
+        builder = builder
+            .manage(updater::PendingUpdate(std::sync::Mutex::new(None)))
+            .manage(updater::StagedUpdate::default())
+            .manage(updater::UpdateCancellation::default())
+            .manage(updater::release_notes::ReleaseNotesCache::default())
+            .manage(updater::schedule::Scheduler::default())
+            .manage(shortcuts::ShortcutErrors::default());
     }
 
     builder = builder
         .plugin(tauri_plugin_sql::Builder::new().build())
         .plugin(tauri_plugin_fs::init())
-        .plugin(tauri_plugin_dialog::init());
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_notification::init());
 
     #[cfg(desktop)]
     {
         builder = builder.plugin(tauri_plugin_updater::Builder::new().build());
+        builder = builder.plugin(tauri_plugin_global_shortcut::Builder::new().build());
+        // Visibility is left out so the window stays hidden until setup shows it
+        builder = builder.plugin(
+            tauri_plugin_window_state::Builder::new()
+                .with_state_flags(
+                    tauri_plugin_window_state::StateFlags::all()
+                        - tauri_plugin_window_state::StateFlags::VISIBLE,
+                )
+                .build(),
+        );
     }
 
     #[cfg(desktop)]
     {
         builder = builder.invoke_handler(tauri::generate_handler![
             db::execute_transaction,
+            db::cancel_transaction,
+            db::query_rows,
+            db::batch_across_dbs,
+            db::query_rows_readonly,
+            db::explain_query_plan,
+            db::configure_pool,
+            db::share_plugin_pool,
+            db::reveal_database,
+            db::close_connection,
+            db::close_all_connections,
+            db::list_connections,
+            db::pool_stats,
+            db::ping_connections,
+            db::execute_batch,
+            db::run_pragma,
+            db::rekey_database,
+            db::migrations::run_migrations,
+            db::attach::attach_database,
+            db::attach::detach_database,
+            db::setup::set_connection_setup,
+            db::setup::get_connection_setup,
+            db::replica::set_read_replica,
+            db::replica::get_read_replica,
+            db::backup::backup_database,
+            db::bulk::bulk_insert,
+            db::vacuum::vacuum_database,
+            db::vacuum::database_stats,
+            db::vacuum::wal_checkpoint,
+            db::dump::dump_sql,
+            db::import::import_sql,
+            db::integrity::check_integrity,
+            db::schema::schema_diff,
+            db::query_log::set_query_log,
+            db::query_log::get_query_log,
+            db::query_log::clear_query_log,
+            db::cursor::open_cursor,
+            db::cursor::fetch_next,
+            db::cursor::close_cursor,
+            db::fts::create_fts_index,
+            db::fts::search_fts,
+            logging::get_log_path,
+            logging::get_log_level,
+            logging::set_log_level,
+            notifications::get_notifications_enabled,
+            notifications::set_notifications_enabled,
+            supports_updates,
+            deep_link::take_pending_deep_links,
+            shortcuts::get_global_shortcuts,
+            shortcuts::set_global_shortcut,
             updater::check_for_update,
+            updater::has_pending_update,
             updater::download_and_install_update,
+            updater::download_update,
+            updater::install_staged_update,
+            updater::cancel_update,
+            updater::restart_app,
+            updater::skip_version,
+            updater::get_current_channel,
+            updater::set_channel,
+            updater::set_update_proxy,
+            updater::set_update_mirrors,
             updater::get_current_version,
+            updater::compare_versions,
+            updater::is_newer,
+            updater::release_notes::get_release_notes,
+            updater::release_notes::list_available_versions,
+            updater::schedule::get_auto_update_enabled,
+            updater::schedule::set_auto_update_enabled,
         ]);
     }
 
     #[cfg(not(desktop))]
     {
-        builder = builder.invoke_handler(tauri::generate_handler![db::execute_transaction]);
+        builder = builder.invoke_handler(tauri::generate_handler![
+            db::execute_transaction,
+            db::cancel_transaction,
+            db::query_rows,
+            db::batch_across_dbs,
+            db::query_rows_readonly,
+            db::explain_query_plan,
+            db::configure_pool,
+            db::share_plugin_pool,
+            db::close_connection,
+            db::close_all_connections,
+            db::list_connections,
+            db::pool_stats,
+            db::ping_connections,
+            db::execute_batch,
+            db::run_pragma,
+            db::rekey_database,
+            db::migrations::run_migrations,
+            db::attach::attach_database,
+            db::attach::detach_database,
+            db::setup::set_connection_setup,
+            db::setup::get_connection_setup,
+            db::replica::set_read_replica,
+            db::replica::get_read_replica,
+            db::backup::backup_database,
+            db::bulk::bulk_insert,
+            db::vacuum::vacuum_database,
+            db::vacuum::database_stats,
+            db::vacuum::wal_checkpoint,
+            db::dump::dump_sql,
+            db::import::import_sql,
+            db::integrity::check_integrity,
+            db::schema::schema_diff,
+            db::query_log::set_query_log,
+            db::query_log::get_query_log,
+            db::query_log::clear_query_log,
+            db::cursor::open_cursor,
+            db::cursor::fetch_next,
+            db::cursor::close_cursor,
+            db::fts::create_fts_index,
+            db::fts::search_fts,
+            logging::get_log_path,
+            logging::get_log_level,
+            logging::set_log_level,
+            notifications::get_notifications_enabled,
+            notifications::set_notifications_enabled,
+            supports_updates,
+        ]);
     }
 
     builder
         .setup(|app| {
-            if cfg!(debug_assertions) {
-                app.handle().plugin(
-                    tauri_plugin_log::Builder::default()
-                        .level(log::LevelFilter::Info)
-                        .build(),
-                )?;
+            app.handle().plugin(logging::plugin())?;
+            logging::apply_configured_level(app.handle());
+
+            #[cfg(desktop)]
+            {
+                updater::validate_endpoints(app.handle());
+                updater::schedule::start(app.handle());
+            }
+
+            #[cfg(desktop)]
+            {
+                tray::create(app.handle())?;
+                shortcuts::register_saved(app.handle());
             }
 
             // Show the main window after setup is complete
             let window = app.get_webview_window("main").unwrap();
+            #[cfg(desktop)]
+            if let Err(err) = window::keep_on_screen(&window) {
+                log::warn!("Failed to check the window position: {}", err);
+            }
             window.show().unwrap();
 
+            // After the window is shown, since a link focuses it
+            #[cfg(desktop)]
+            deep_link::setup(app.handle());
+
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                db::close_all_on_exit(app);
+            }
+        });
 }
@@ -0,0 +1,105 @@
+//! Log output to the console and a rotating file in the app log directory
+//!
+//! Release builds log to a file too, so a user can attach it to a bug report.
+//! Every record is passed to the log plugin and the level is applied with
+//! `log::set_max_level`, which lets set_log_level change it without a restart.
+//! The chosen level is kept in `logging.json` in the app config directory.
+
+use std::str::FromStr;
+
+use log::LevelFilter;
+use serde::{Deserialize, Serialize};
+use tauri::plugin::TauriPlugin;
+use tauri::{AppHandle, Manager, Runtime};
+use tauri_plugin_log::{RotationStrategy, Target, TargetKind};
+
+use crate::config;
+
+const SETTINGS_FILE: &str = "logging.json";
+/// Name of the log file, without the `.log` extension
+const LOG_FILE: &str = "invariant";
+/// Start a new file once the current one reaches this size
+const MAX_FILE_SIZE: u128 = 5 * 1024 * 1024;
+/// Rotated files kept alongside the current one
+const KEEP_FILES: usize = 4;
+
+#[cfg(debug_assertions)]
+const DEFAULT_LEVEL: LevelFilter = LevelFilter::Info;
+#[cfg(not(debug_assertions))]
+const DEFAULT_LEVEL: LevelFilter = LevelFilter::Warn;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct LogSettings {
+    /// "off", "error", "warn", "info", "debug" or "trace"
+    level: Option<String>,
+}
+
+/// The saved log level, or the default for this build
+fn configured_level(app: &AppHandle) -> LevelFilter {
+    config::load::<LogSettings>(app, SETTINGS_FILE)
+        .level
+        .and_then(|level| LevelFilter::from_str(&level).ok())
+        .unwrap_or(DEFAULT_LEVEL)
+}
+
+/// The log plugin, writing to stdout and the rotating log file
+pub fn plugin<R: Runtime>() -> TauriPlugin<R> {
+    tauri_plugin_log::Builder::new()
+        .targets([
+            Target::new(TargetKind::Stdout),
+            Target::new(TargetKind::LogDir {
+                file_name: Some(LOG_FILE.to_string()),
+            }),
+        ])
+        .max_file_size(MAX_FILE_SIZE)
+        .rotation_strategy(RotationStrategy::KeepSome(KEEP_FILES))
+        // Filtering happens through log::max_level, so the level can change at runtime
+        .level(LevelFilter::Trace)
+        .build()
+}
+
+/// Apply the saved log level; call once the log plugin is registered
+pub fn apply_configured_level(app: &AppHandle) {
+    log::set_max_level(configured_level(app));
+}
+
+/// Path of the current log file, for attaching to a bug report
+#[tauri::command]
+pub fn get_log_path(app: AppHandle) -> Result<String, String> {
+    app.path()
+        .app_log_dir()
+        .map(|dir| {
+            dir.join(format!("{}.log", LOG_FILE))
+                .to_string_lossy()
+                .into_owned()
+        })
+        .map_err(|e| format!("Failed to find the log directory: {}", e))
+}
+
+/// The log level in effect ("off", "error", "warn", "info", "debug" or "trace")
+#[tauri::command]
+pub fn get_log_level() -> String {
+    log::max_level().as_str().to_lowercase()
+}
+
+/// Change the log level now and for future launches
+#[tauri::command]
+pub fn set_log_level(app: AppHandle, level: String) -> Result<(), String> {
+    let filter = LevelFilter::from_str(level.trim()).map_err(|_| {
+        format!(
+            "Invalid log level '{}': expected off, error, warn, info, debug or trace",
+            level
+        )
+    })?;
+
+    let settings = LogSettings {
+        level: Some(filter.as_str().to_lowercase()),
+    };
+    config::save(&app, SETTINGS_FILE, &settings)
+        .map_err(|e| format!("Failed to save log settings: {}", e))?;
+
+    log::set_max_level(filter);
+    log::info!("Log level set to {}", filter);
+    Ok(())
+}
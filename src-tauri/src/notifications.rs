@@ -0,0 +1,80 @@
+//! Native OS notifications
+//!
+//! A few things finish while the user may be looking at another app: an
+//! update downloaded or installed without restarting, and a long import.
+//! These show a system notification unless the user turned notifications off
+//! with set_notifications_enabled; the choice is kept in `notifications.json`
+//! in the app config directory.
+//!
+//! Notifications are a courtesy, so when the OS doesn't allow them (macOS can
+//! deny them per app) the notification is logged and dropped.
+
+use serde::{Deserialize, Serialize};
+use tauri::plugin::PermissionState;
+use tauri::AppHandle;
+use tauri_plugin_notification::NotificationExt;
+
+use crate::config;
+
+const SETTINGS_FILE: &str = "notifications.json";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct NotificationSettings {
+    /// Show notifications; on when unset
+    enabled: Option<bool>,
+}
+
+/// Whether the user wants notifications
+fn enabled(app: &AppHandle) -> bool {
+    config::load::<NotificationSettings>(app, SETTINGS_FILE)
+        .enabled
+        .unwrap_or(true)
+}
+
+/// Show a notification, if the user wants them and the OS allows them
+pub fn notify(app: &AppHandle, title: &str, body: &str) {
+    if !enabled(app) {
+        return;
+    }
+
+    let notification = app.notification();
+    let permitted = match notification.permission_state() {
+        Ok(PermissionState::Granted) => true,
+        Ok(PermissionState::Prompt | PermissionState::PromptWithRationale) => matches!(
+            notification.request_permission(),
+            Ok(PermissionState::Granted)
+        ),
+        Ok(PermissionState::Denied) | Err(_) => false,
+    };
+    if !permitted {
+        log::info!("Notifications aren't permitted; not showing '{}'", title);
+        return;
+    }
+
+    if let Err(err) = notification.builder().title(title).body(body).show() {
+        log::warn!("Failed to show notification '{}': {}", title, err);
+    }
+}
+
+/// Whether notifications are on
+#[tauri::command]
+pub fn get_notifications_enabled(app: AppHandle) -> bool {
+    enabled(&app)
+}
+
+/// Turn notifications on or off, now and for future launches
+#[tauri::command]
+pub fn set_notifications_enabled(app: AppHandle, enabled: bool) -> Result<(), String> {
+    let settings = NotificationSettings {
+        enabled: Some(enabled),
+    };
+    config::save(&app, SETTINGS_FILE, &settings)
+        .map_err(|e| format!("Failed to save notification settings: {}", e))?;
+
+    log::info!(
+        "Notifications {}",
+        if enabled { "enabled" } else { "disabled" }
+    );
+    Ok(())
+}
@@ -0,0 +1,232 @@
+//! Global keyboard shortcuts
+//!
+//! A shortcut works even while another app has focus: one brings up the main
+//! window and one checks for updates. Neither is set until the user picks an
+//! accelerator such as `CmdOrCtrl+Shift+I`, since any default could take a key
+//! combination another app relies on. The chosen accelerators are kept in
+//! `shortcuts.json` in the app config directory and registered at startup.
+//!
+//! Registering fails when another app already holds the key combination. At
+//! startup that failure is kept for get_global_shortcuts to report, so the
+//! settings screen can ask for a different one.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+
+use crate::{config, tray, window};
+
+const SETTINGS_FILE: &str = "shortcuts.json";
+
+/// What a global shortcut does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ShortcutAction {
+    /// Show and focus the main window
+    ShowWindow,
+    /// Check for updates on the saved channel, like the tray item
+    CheckForUpdates,
+}
+
+impl ShortcutAction {
+    const ALL: [ShortcutAction; 2] = [ShortcutAction::ShowWindow, ShortcutAction::CheckForUpdates];
+
+    fn name(self) -> &'static str {
+        match self {
+            ShortcutAction::ShowWindow => "showing the window",
+            ShortcutAction::CheckForUpdates => "checking for updates",
+        }
+    }
+
+    fn run(self, app: &AppHandle) {
+        match self {
+            ShortcutAction::ShowWindow => window::focus_main(app),
+            ShortcutAction::CheckForUpdates => tray::check_for_updates(app),
+        }
+    }
+}
+
+/// Accelerators by action, as saved in shortcuts.json
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct ShortcutSettings(BTreeMap<ShortcutAction, String>);
+
+/// Why a saved shortcut couldn't be registered at startup, by action
+#[derive(Default)]
+pub struct ShortcutErrors(Mutex<BTreeMap<ShortcutAction, String>>);
+
+/// A shortcut as reported to the settings screen
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GlobalShortcut {
+    pub action: ShortcutAction,
+    /// The saved accelerator; None when the action has no shortcut
+    pub accelerator: Option<String>,
+    /// Whether the shortcut is active; false when registering it failed
+    pub registered: bool,
+    pub error: Option<String>,
+}
+
+impl ShortcutSettings {
+    /// Read the saved shortcuts, with none if the file is missing or unreadable
+    fn load(app: &AppHandle) -> Self {
+        config::load(app, SETTINGS_FILE)
+    }
+
+    fn save(&self, app: &AppHandle) -> Result<(), String> {
+        config::save(app, SETTINGS_FILE, self)
+            .map_err(|e| format!("Failed to save shortcuts: {}", e))
+    }
+}
+
+fn parse(accelerator: &str) -> Result<Shortcut, String> {
+    Shortcut::from_str(accelerator)
+        .map_err(|e| format!("Invalid shortcut '{}': {}", accelerator, e))
+}
+
+/// Register `shortcut` to run `action` when pressed
+fn register(app: &AppHandle, action: ShortcutAction, shortcut: Shortcut) -> Result<(), String> {
+    app.global_shortcut()
+        .on_shortcut(shortcut, move |app, _, event| {
+            if event.state == ShortcutState::Pressed {
+                action.run(app);
+            }
+        })
+        .map_err(|e| {
+            format!(
+                "Couldn't register {}; another app may already use it ({})",
+                shortcut.into_string(),
+                e
+            )
+        })
+}
+
+/// Register the saved shortcuts; call once the global-shortcut plugin is set up
+pub fn register_saved(app: &AppHandle) {
+    let settings = ShortcutSettings::load(app);
+    let errors = app.state::<ShortcutErrors>();
+    let Ok(mut errors) = errors.0.lock() else {
+        return;
+    };
+
+    for (action, accelerator) in &settings.0 {
+        if let Err(err) = parse(accelerator).and_then(|shortcut| register(app, *action, shortcut)) {
+            log::warn!(
+                "Global shortcut for {} not registered: {}",
+                action.name(),
+                err
+            );
+            errors.insert(*action, err);
+        }
+    }
+}
+
+/// Every action with its shortcut and whether it is registered
+#[tauri::command]
+pub fn get_global_shortcuts(
+    app: AppHandle,
+    errors: State<'_, ShortcutErrors>,
+) -> Result<Vec<GlobalShortcut>, String> {
+    let settings = ShortcutSettings::load(&app);
+    let errors = errors
+        .0
+        .lock()
+        .map_err(|_| "Shortcut state is unavailable".to_string())?;
+
+    Ok(ShortcutAction::ALL
+        .iter()
+        .map(|action| {
+            let accelerator = settings.0.get(action).cloned();
+            let registered = accelerator
+                .as_deref()
+                .is_some_and(|accelerator| app.global_shortcut().is_registered(accelerator));
+            GlobalShortcut {
+                action: *action,
+                accelerator,
+                registered,
+                error: errors.get(action).cloned(),
+            }
+        })
+        .collect())
+}
+
+/// Change the shortcut for an action now and for future launches
+///
+/// If the new shortcut can't be registered, for example because another app
+/// already uses it, the previous one stays in place and the error says why.
+///
+/// # Arguments
+/// * `accelerator` - For example `CmdOrCtrl+Shift+I`; None or empty removes the shortcut
+#[tauri::command]
+pub async fn set_global_shortcut(
+    app: AppHandle,
+    action: ShortcutAction,
+    accelerator: Option<String>,
+    errors: State<'_, ShortcutErrors>,
+) -> Result<(), String> {
+    let accelerator = accelerator
+        .map(|accelerator| accelerator.trim().to_string())
+        .filter(|accelerator| !accelerator.is_empty());
+    let shortcut = accelerator.as_deref().map(parse).transpose()?;
+
+    let mut settings = ShortcutSettings::load(&app);
+    if let Some(shortcut) = shortcut {
+        let taken_by = settings.0.iter().find(|(other, saved)| {
+            **other != action && parse(saved).is_ok_and(|saved| saved.id() == shortcut.id())
+        });
+        if let Some((other, _)) = taken_by {
+            return Err(format!(
+                "{} is already the shortcut for {}",
+                shortcut.into_string(),
+                other.name()
+            ));
+        }
+    }
+
+    let previous = settings
+        .0
+        .get(&action)
+        .and_then(|saved| parse(saved).ok())
+        .filter(|previous| app.global_shortcut().is_registered(*previous));
+    if let Some(previous) = previous {
+        app.global_shortcut()
+            .unregister(previous)
+            .map_err(|e| format!("Failed to remove the previous shortcut: {}", e))?;
+    }
+
+    if let Some(shortcut) = shortcut {
+        if let Err(err) = register(&app, action, shortcut) {
+            if let Some(previous) = previous {
+                if let Err(restore) = register(&app, action, previous) {
+                    log::warn!("Failed to restore the previous shortcut: {}", restore);
+                }
+            }
+            return Err(err);
+        }
+    }
+
+    match accelerator {
+        Some(accelerator) => {
+            log::info!(
+                "Global shortcut for {} set to {}",
+                action.name(),
+                accelerator
+            );
+            settings.0.insert(action, accelerator);
+        }
+        None => {
+            log::info!("Global shortcut for {} removed", action.name());
+            settings.0.remove(&action);
+        }
+    }
+    settings.save(&app)?;
+
+    if let Ok(mut errors) = errors.0.lock() {
+        errors.remove(&action);
+    }
+    Ok(())
+}
@@ -0,0 +1,119 @@
+//! System tray icon
+//!
+//! The tray keeps a few actions within reach while the window is hidden:
+//! opening the window, checking for updates and quitting. When a check finds
+//! an update, the tooltip and the check item name the new version.
+
+use tauri::menu::{Menu, MenuItem, PredefinedMenuItem};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
+use tauri::{AppHandle, Emitter, Listener, Manager};
+
+use crate::updater::release_notes::ReleaseNotesCache;
+use crate::updater::schedule::UPDATE_AVAILABLE_EVENT;
+use crate::updater::{self, PendingUpdate, UpdateMetadata};
+use crate::window;
+
+const TRAY_ID: &str = "main";
+const TOOLTIP: &str = "Invariant";
+
+const OPEN: &str = "open";
+const CHECK_FOR_UPDATES: &str = "check-for-updates";
+const QUIT: &str = "quit";
+
+/// Check for updates on the saved channel and offer any update in the main window
+///
+/// Unlike the background checks this is a deliberate request, so a skipped
+/// version is still offered.
+pub(crate) fn check_for_updates(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let pending_update = app.state::<PendingUpdate>();
+        let release_notes = app.state::<ReleaseNotesCache>();
+        let result = updater::check_for_update(
+            app.clone(),
+            pending_update,
+            release_notes,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await;
+        match result {
+            Ok(Some(metadata)) => {
+                window::focus_main(&app);
+                let _ = app.emit(UPDATE_AVAILABLE_EVENT, metadata);
+            }
+            Ok(None) | Err(updater::Error::DowngradeNotAllowed { .. }) => {
+                set_tooltip(&app, "Invariant is up to date")
+            }
+            Err(err) => {
+                log::warn!("Update check from the tray failed: {}", err);
+                set_tooltip(&app, "Invariant couldn't check for updates");
+            }
+        }
+    });
+}
+
+fn set_tooltip(app: &AppHandle, tooltip: &str) {
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        let _ = tray.set_tooltip(Some(tooltip));
+    }
+}
+
+/// Add the tray icon and its menu
+pub fn create(app: &AppHandle) -> tauri::Result<()> {
+    let open = MenuItem::with_id(app, OPEN, "Open", true, None::<&str>)?;
+    let check = MenuItem::with_id(
+        app,
+        CHECK_FOR_UPDATES,
+        "Check for updates",
+        true,
+        None::<&str>,
+    )?;
+    let quit = MenuItem::with_id(app, QUIT, "Quit", true, None::<&str>)?;
+    let menu = Menu::with_items(
+        app,
+        &[&open, &check, &PredefinedMenuItem::separator(app)?, &quit],
+    )?;
+
+    let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+        .tooltip(TOOLTIP)
+        .menu(&menu)
+        .show_menu_on_left_click(false)
+        .on_menu_event(|app, event| match event.id().as_ref() {
+            OPEN => window::focus_main(app),
+            CHECK_FOR_UPDATES => check_for_updates(app),
+            QUIT => app.exit(0),
+            _ => {}
+        })
+        .on_tray_icon_event(|tray, event| {
+            if let TrayIconEvent::Click {
+                button: MouseButton::Left,
+                button_state: MouseButtonState::Up,
+                ..
+            } = event
+            {
+                window::focus_main(tray.app_handle());
+            }
+        });
+    if let Some(icon) = app.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    builder.build(app)?;
+
+    // Background checks and the check above both announce updates with this event
+    let handle = app.clone();
+    app.listen(UPDATE_AVAILABLE_EVENT, move |event| {
+        let Ok(metadata) = serde_json::from_str::<UpdateMetadata>(event.payload()) else {
+            return;
+        };
+        set_tooltip(
+            &handle,
+            &format!("Invariant: version {} is available", metadata.version),
+        );
+        let _ = check.set_text(format!("Update to {}...", metadata.version));
+    });
+
+    Ok(())
+}
@@ -1,11 +1,22 @@
 //! Update management module
 //!
 //! Handles checking for updates, downloading, and installing them.
-//! Supports stable and beta release channels.
+//! Supports stable, beta, nightly and alpha release channels, plus custom
+//! channels published under their own tag.
 
+mod download;
+pub mod release_notes;
+pub mod schedule;
+mod settings;
+
+use serde::ser::SerializeStruct;
 use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
-use tauri::{AppHandle, Emitter, State};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Listener, State};
+
+use settings::UpdaterSettings;
 
 #[cfg(desktop)]
 use tauri_plugin_updater::{Update, UpdaterExt};
@@ -16,8 +27,38 @@ pub enum Error {
     #[cfg(desktop)]
     #[error("updater error: {0}")]
     Updater(String),
+    #[error("couldn't reach update server: {0}")]
+    Unreachable(String),
+    #[error("update server didn't respond within {0} seconds")]
+    Timeout(u64),
+    #[error("update server returned an invalid manifest from {url}: {reason}")]
+    InvalidManifest { url: String, reason: String },
+    #[error("invalid release channel: {0}")]
+    InvalidChannel(String),
+    #[error("invalid proxy {url}: {reason}")]
+    InvalidProxy { url: String, reason: String },
+    #[error("invalid update endpoint {url}: {reason}")]
+    InvalidEndpoint { url: String, reason: String },
+    #[error("invalid version {version}: {reason}")]
+    InvalidVersion { version: String, reason: String },
+    #[error("download failed: {0}")]
+    Download(String),
+    #[error("failed to fetch the release list: {0}")]
+    Releases(String),
+    #[error("version {0} isn't available to install")]
+    VersionNotFound(String),
+    #[error("failed to save updater settings: {0}")]
+    Settings(String),
     #[error("there is no pending update")]
     NoPendingUpdate,
+    #[error("there is no downloaded update to install")]
+    NoStagedUpdate,
+    #[error("update cancelled")]
+    Cancelled,
+    #[error("update checksum mismatch: expected {expected}, got {actual}")]
+    ChecksumMismatch { expected: String, actual: String },
+    #[error("available version {available} is older than installed {installed}; downgrade not allowed")]
+    DowngradeNotAllowed { available: String, installed: String },
 }
 
 #[cfg(desktop)]
@@ -27,17 +68,42 @@ impl From<tauri_plugin_updater::Error> for Error {
     }
 }
 
+impl Error {
+    /// Stable name of the error for the frontend to branch on, unlike the message
+    fn kind(&self) -> &'static str {
+        match self {
+            #[cfg(desktop)]
+            Error::Updater(_) => "updater",
+            Error::Unreachable(_) => "unreachable",
+            Error::Timeout(_) => "timeout",
+            Error::InvalidManifest { .. } => "invalidManifest",
+            Error::InvalidChannel(_) => "invalidChannel",
+            Error::InvalidProxy { .. } => "invalidProxy",
+            Error::InvalidEndpoint { .. } => "invalidEndpoint",
+            Error::InvalidVersion { .. } => "invalidVersion",
+            Error::Download(_) => "download",
+            Error::Releases(_) => "releases",
+            Error::VersionNotFound(_) => "versionNotFound",
+            Error::Settings(_) => "settings",
+            Error::NoPendingUpdate => "noPendingUpdate",
+            Error::NoStagedUpdate => "noStagedUpdate",
+            Error::Cancelled => "cancelled",
+            Error::ChecksumMismatch { .. } => "checksumMismatch",
+            Error::DowngradeNotAllowed { .. } => "downgradeNotAllowed",
+        }
+    }
+}
+
+/// Serialized as `{ kind, message }`, like the database commands' errors
 impl Serialize for Error {
     fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        let msg = match self {
-            #[cfg(desktop)]
-            Error::Updater(s) => format!("updater error: {}", s),
-            Error::NoPendingUpdate => "there is no pending update".to_string(),
-        };
-        serializer.serialize_str(&msg)
+        let mut state = serializer.serialize_struct("Error", 2)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
     }
 }
 
@@ -48,10 +114,47 @@ type Result<T> = std::result::Result<T, Error>;
 #[serde(tag = "event", content = "data")]
 pub enum DownloadEvent {
     #[serde(rename_all = "camelCase")]
-    Started { content_length: Option<u64> },
+    Started {
+        /// Size of the whole download, if the server reported it
+        content_length: Option<u64>,
+        /// Bytes kept from an earlier, interrupted download
+        resumed_from: u64,
+    },
+    /// Sent at most every 100ms, and for the last chunk
     #[serde(rename_all = "camelCase")]
-    Progress { chunk_length: usize },
+    Progress {
+        /// Bytes received since the previous Progress event
+        chunk_length: usize,
+        /// Bytes downloaded so far, including this chunk and any resumed bytes
+        downloaded: u64,
+        /// Size of the whole download, if the server reported it
+        total: Option<u64>,
+        /// Average speed since this attempt started
+        bytes_per_second: f64,
+        /// Estimated time left at the average speed, if the size is known
+        eta_seconds: Option<f64>,
+    },
+    /// The download is complete and its signature and checksum are being checked
+    Verifying,
     Finished,
+    Cancelled,
+}
+
+/// Event emitted with UpdateRestarting just before the app restarts into an update
+pub const UPDATE_RESTARTING_EVENT: &str = "update-restarting";
+/// Event the frontend emits once it has saved its state and the restart can go ahead
+pub const UPDATE_RESTART_READY_EVENT: &str = "update-restart-ready";
+/// Longest the restart waits for `update-restart-ready`
+const RESTART_GRACE_PERIOD: Duration = Duration::from_secs(3);
+
+/// Payload of `update-restarting`
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateRestarting {
+    /// The version the app is restarting into
+    pub version: String,
+    /// How long the restart waits for `update-restart-ready` before going ahead
+    pub grace_period_ms: u64,
 }
 
 /// Update metadata returned to frontend
@@ -62,27 +165,280 @@ pub struct UpdateMetadata {
     pub current_version: String,
     pub date: Option<String>,
     pub body: Option<String>,
+    /// The user chose to skip this version (or a newer one) with skip_version
+    pub skipped: bool,
+    /// SHA-256 of the package from the manifest (hex), checked after download
+    pub checksum: Option<String>,
+}
+
+#[cfg(desktop)]
+impl UpdateMetadata {
+    fn new(update: &Update, skipped_version: Option<&str>) -> Self {
+        Self {
+            version: update.version.clone(),
+            current_version: update.current_version.clone(),
+            date: update.date.as_ref().map(|d| d.to_string()),
+            body: update.body.clone(),
+            skipped: is_skipped(&update.version, skipped_version),
+            checksum: expected_checksum(update),
+        }
+    }
+}
+
+/// The package's SHA-256 from the manifest, if it has one
+///
+/// Read from `sha256` next to the platform's `url` and `signature`, or from a
+/// top-level `sha256` for single-platform manifests.
+#[cfg(desktop)]
+fn expected_checksum(update: &Update) -> Option<String> {
+    let platform_checksum = update
+        .raw_json
+        .get("platforms")
+        .and_then(|platforms| platforms.get(&update.target))
+        .and_then(|platform| platform.get("sha256"));
+
+    platform_checksum
+        .or_else(|| update.raw_json.get("sha256"))
+        .and_then(|checksum| checksum.as_str())
+        .map(|checksum| checksum.trim().to_ascii_lowercase())
 }
 
 /// Release channel type
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ReleaseChannel {
     Stable,
     Beta,
+    Nightly,
+    Alpha,
+    /// Any other channel, published under a `latest-<name>` release tag
+    Custom(String),
 }
 
 impl ReleaseChannel {
     pub fn from_str(s: &str) -> Self {
-        match s.to_lowercase().as_str() {
+        let name = s.trim().to_lowercase();
+        match name.as_str() {
+            "" | "stable" => ReleaseChannel::Stable,
             "beta" => ReleaseChannel::Beta,
-            _ => ReleaseChannel::Stable,
+            "nightly" => ReleaseChannel::Nightly,
+            "alpha" => ReleaseChannel::Alpha,
+            _ => ReleaseChannel::Custom(name),
         }
     }
 
-    pub fn to_str(&self) -> &'static str {
+    pub fn to_str(&self) -> &str {
         match self {
             ReleaseChannel::Stable => "stable",
             ReleaseChannel::Beta => "beta",
+            ReleaseChannel::Nightly => "nightly",
+            ReleaseChannel::Alpha => "alpha",
+            ReleaseChannel::Custom(name) => name,
+        }
+    }
+
+    /// Update manifest URL for this channel in the GitHub repository `repo`
+    /// (owner/name), or None for the default endpoint configured in
+    /// tauri.conf.json
+    pub fn endpoint(&self, repo: &str) -> Option<String> {
+        match self {
+            ReleaseChannel::Stable => None,
+            channel => Some(format!(
+                "https://github.com/{}/releases/download/latest-{}/latest.json",
+                repo,
+                channel.to_str()
+            )),
+        }
+    }
+}
+
+/// Parse an update manifest URL
+fn parse_endpoint(url: &str) -> Result<tauri::Url> {
+    let endpoint = tauri::Url::parse(url).map_err(|e| Error::InvalidEndpoint {
+        url: url.to_string(),
+        reason: e.to_string(),
+    })?;
+
+    if !matches!(endpoint.scheme(), "http" | "https") {
+        return Err(Error::InvalidEndpoint {
+            url: url.to_string(),
+            reason: "only http and https endpoints are supported".to_string(),
+        });
+    }
+
+    Ok(endpoint)
+}
+
+/// The default endpoints from the updater section of tauri.conf.json
+fn configured_endpoints(app: &AppHandle) -> Vec<tauri::Url> {
+    app.config()
+        .plugins
+        .0
+        .get("updater")
+        .and_then(|updater| updater.get("endpoints"))
+        .and_then(|endpoints| endpoints.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|url| url.as_str())
+        .filter_map(|url| parse_endpoint(url).ok())
+        .collect()
+}
+
+/// Repository releases come from when neither the build nor tauri.conf.json says
+const DEFAULT_RELEASE_REPO: &str = "yorphos/invariant";
+
+/// The GitHub repository releases are published to, as owner/name
+///
+/// `INVARIANT_RELEASE_REPO` set when building takes precedence, so a fork or a
+/// staging build can point elsewhere without editing the config. Otherwise the
+/// repository is read from the GitHub endpoint in tauri.conf.json's updater
+/// section.
+pub(crate) fn release_repo(app: &AppHandle) -> String {
+    if let Some(repo) = option_env!("INVARIANT_RELEASE_REPO")
+        .map(|repo| repo.trim().trim_matches('/'))
+        .filter(|repo| !repo.is_empty())
+    {
+        return repo.to_string();
+    }
+
+    configured_endpoints(app)
+        .iter()
+        .filter(|url| url.host_str() == Some("github.com"))
+        .find_map(|url| {
+            let mut segments = url.path_segments()?;
+            match (segments.next(), segments.next(), segments.next()) {
+                (Some(owner), Some(name), Some("releases"))
+                    if !owner.is_empty() && !name.is_empty() =>
+                {
+                    Some(format!("{}/{}", owner, name))
+                }
+                _ => None,
+            }
+        })
+        .unwrap_or_else(|| DEFAULT_RELEASE_REPO.to_string())
+}
+
+/// Manifest URLs to try in order: the override if there is one, otherwise the
+/// channel's endpoint followed by any mirrors saved with set_update_mirrors
+#[cfg(desktop)]
+fn update_endpoints(
+    app: &AppHandle,
+    release_channel: &ReleaseChannel,
+    endpoint_override: Option<String>,
+    settings: &UpdaterSettings,
+) -> Result<Vec<tauri::Url>> {
+    if let Some(endpoint) = endpoint_override.filter(|url| !url.trim().is_empty()) {
+        return Ok(vec![parse_endpoint(endpoint.trim())?]);
+    }
+
+    // Pre-release channels are published under their own GitHub release tag;
+    // stable uses the default endpoint from tauri.conf.json
+    let mut endpoints = match release_channel.endpoint(&release_repo(app)) {
+        Some(endpoint) => vec![parse_endpoint(&endpoint)?],
+        None => configured_endpoints(app),
+    };
+    for mirror in settings.mirrors(release_channel) {
+        endpoints.push(parse_endpoint(mirror)?);
+    }
+
+    if endpoints.is_empty() {
+        return Err(Error::InvalidEndpoint {
+            url: String::new(),
+            reason: format!(
+                "no update endpoint is configured for the {} channel",
+                release_channel.to_str()
+            ),
+        });
+    }
+    Ok(endpoints)
+}
+
+/// Parse a channel name, rejecting names that can't be part of a release tag
+fn parse_channel(channel: &str) -> Result<ReleaseChannel> {
+    let valid = channel
+        .trim()
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        return Err(Error::InvalidChannel(channel.to_string()));
+    }
+
+    Ok(ReleaseChannel::from_str(channel))
+}
+
+/// Parse a proxy URL such as `http://proxy.example.com:8080`
+fn parse_proxy(url: &str) -> Result<tauri::Url> {
+    let invalid = |reason: String| Error::InvalidProxy {
+        url: url.to_string(),
+        reason,
+    };
+
+    let proxy = tauri::Url::parse(url.trim()).map_err(|e| invalid(e.to_string()))?;
+    if !matches!(proxy.scheme(), "http" | "https") {
+        return Err(invalid("only http and https proxies are supported".to_string()));
+    }
+    if proxy.host_str().is_none() {
+        return Err(invalid("the proxy URL has no host".to_string()));
+    }
+
+    Ok(proxy)
+}
+
+/// Start an HTTP client for update traffic, going through `proxy` if set
+fn http_client(
+    app: &AppHandle,
+    proxy: Option<&tauri::Url>,
+) -> reqwest::Result<reqwest::ClientBuilder> {
+    // reqwest is built without a default TLS provider, as the updater plugin is
+    if rustls::crypto::CryptoProvider::get_default().is_none() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+    }
+
+    let mut builder =
+        reqwest::Client::builder().user_agent(format!("invariant/{}", app.package_info().version));
+    if let Some(proxy) = proxy {
+        builder = builder.proxy(reqwest::Proxy::all(proxy.as_str())?);
+    }
+    Ok(builder)
+}
+
+/// Parse a version string, allowing a leading `v` as used in release tags
+fn parse_version(version: &str) -> Result<semver::Version> {
+    let trimmed = version.trim();
+    let trimmed = trimmed.strip_prefix('v').unwrap_or(trimmed);
+    semver::Version::parse(trimmed).map_err(|e| Error::InvalidVersion {
+        version: version.to_string(),
+        reason: e.to_string(),
+    })
+}
+
+/// Whether `version` is at or below the version the user chose to skip
+fn is_skipped(version: &str, skipped_version: Option<&str>) -> bool {
+    let Some(skipped_version) = skipped_version else {
+        return false;
+    };
+
+    match (parse_version(version), parse_version(skipped_version)) {
+        (Ok(version), Ok(skipped)) => version.cmp_precedence(&skipped).is_le(),
+        _ => version == skipped_version,
+    }
+}
+
+/// Check the built-in channel endpoints at startup
+///
+/// A bad endpoint only fails the update check for its channel, but logging it
+/// here catches a broken release URL before anyone switches channels.
+pub fn validate_endpoints(app: &AppHandle) {
+    let repo = release_repo(app);
+    let channels = [
+        ReleaseChannel::Stable,
+        ReleaseChannel::Beta,
+        ReleaseChannel::Nightly,
+        ReleaseChannel::Alpha,
+    ];
+
+    for channel in channels {
+        if let Some(Err(err)) = channel.endpoint(&repo).map(|url| parse_endpoint(&url)) {
+            log::error!("Update channel {} is misconfigured: {}", channel.to_str(), err);
         }
     }
 }
@@ -94,48 +450,284 @@ pub struct PendingUpdate(pub Mutex<Option<Update>>);
 #[cfg(not(desktop))]
 pub struct PendingUpdate(pub Mutex<Option<()>>);
 
+/// An update downloaded by download_update, with its verified package
+#[derive(Default)]
+pub struct StagedUpdate(Mutex<Option<(Update, Vec<u8>)>>);
+
+/// Set by cancel_update to stop the download in progress
+#[derive(Default)]
+pub struct UpdateCancellation {
+    requested: AtomicBool,
+    notify: tokio::sync::Notify,
+}
+
+impl UpdateCancellation {
+    fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+
+    /// Resolves once cancellation has been requested
+    async fn requested(&self) {
+        loop {
+            // Created before the check so a notification in between isn't missed
+            let notified = self.notify.notified();
+            if self.is_requested() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// Retries after a network failure when checking for updates
+const DEFAULT_CHECK_RETRIES: u32 = 3;
+
+/// Delay before the first retry; doubled for each retry after that
+const DEFAULT_RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Longest an update check may take, including retries and fallback endpoints
+const DEFAULT_CHECK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Whether a failed update check is worth retrying
+fn is_network_error(err: &tauri_plugin_updater::Error) -> bool {
+    match err {
+        // A body that isn't JSON arrives as a reqwest decode error
+        tauri_plugin_updater::Error::Reqwest(err) => !err.is_decode(),
+        tauri_plugin_updater::Error::Network(_) => true,
+        _ => false,
+    }
+}
+
+/// Whether an update check failed because the endpoint's manifest couldn't be read
+fn is_manifest_error(err: &tauri_plugin_updater::Error) -> bool {
+    match err {
+        tauri_plugin_updater::Error::Reqwest(err) => err.is_decode(),
+        tauri_plugin_updater::Error::Serialization(_)
+        | tauri_plugin_updater::Error::TargetNotFound(_)
+        | tauri_plugin_updater::Error::TargetsNotFound(_) => true,
+        _ => false,
+    }
+}
+
+/// Check a channel for an update, without storing it as the pending update
+///
+/// Each endpoint is tried in turn until one returns a valid manifest. If none
+/// does and one of them returned a manifest that isn't valid JSON, lacks
+/// required fields or has no entry for this platform, the check fails with
+/// `Error::InvalidManifest` naming that endpoint. When every endpoint failed
+/// to respond, the round is retried with exponential backoff, as configured
+/// by `checkRetries` and `retryBackoffMs` in the updater settings, and
+/// reported as `Error::Unreachable` once the retries run out. The whole check
+/// fails with `Error::Timeout` after `checkTimeoutSecs` (30 by default).
+///
+/// Any release other than the installed version is considered, so a channel
+/// whose latest release is older than the installed one (such as stable,
+/// after running a beta) fails with `Error::DowngradeNotAllowed` unless
+/// `allow_downgrade` is set.
+async fn check(
+    app: &AppHandle,
+    release_channel: &ReleaseChannel,
+    endpoint_override: Option<String>,
+    allow_downgrade: bool,
+) -> Result<(Option<Update>, Option<UpdateMetadata>)> {
+    log::info!("Checking for updates on channel: {}", release_channel.to_str());
+
+    let settings = UpdaterSettings::load(app);
+    let endpoints = update_endpoints(app, release_channel, endpoint_override, &settings)?;
+
+    // Without an explicit proxy, reqwest uses HTTP_PROXY/HTTPS_PROXY and the system settings.
+    // The update's download uses the same proxy.
+    let proxy = settings.proxy.as_deref().map(parse_proxy).transpose()?;
+    let mut updaters = Vec::with_capacity(endpoints.len());
+    for endpoint in &endpoints {
+        let mut builder = app
+            .updater_builder()
+            .version_comparator(|current, release| {
+                release.version.cmp_precedence(&current).is_ne()
+            })
+            .endpoints(vec![endpoint.clone()])?;
+        if let Some(proxy) = &proxy {
+            builder = builder.proxy(proxy.clone());
+        }
+        updaters.push((endpoint, builder.build()?));
+    }
+
+    let retries = settings.check_retries.unwrap_or(DEFAULT_CHECK_RETRIES);
+    let mut backoff = settings
+        .retry_backoff_ms
+        .map_or(DEFAULT_RETRY_BACKOFF, Duration::from_millis);
+
+    let attempts = async {
+        let mut attempt = 0;
+        'attempts: loop {
+            let mut network_error = None;
+            let mut manifest_error = None;
+            let mut other_error = None;
+            for (endpoint, updater) in &updaters {
+                match updater.check().await {
+                    Ok(update) => {
+                        log::info!("Update check succeeded using endpoint: {}", endpoint);
+                        break 'attempts Ok(update);
+                    }
+                    Err(err) => {
+                        log::warn!("Update check using {} failed: {}", endpoint, err);
+                        if is_network_error(&err) {
+                            network_error = Some(err);
+                        } else if is_manifest_error(&err) {
+                            manifest_error = Some(Error::InvalidManifest {
+                                url: endpoint.to_string(),
+                                reason: err.to_string(),
+                            });
+                        } else {
+                            other_error = Some(err);
+                        }
+                    }
+                }
+            }
+
+            // An endpoint that responded with a bad manifest won't fix itself on retry
+            if let Some(err) = manifest_error {
+                return Err(err);
+            }
+            if let Some(err) = other_error {
+                return Err(err.into());
+            }
+            let err = network_error.expect("at least one endpoint was tried");
+            if attempt >= retries {
+                return Err(Error::Unreachable(err.to_string()));
+            }
+            attempt += 1;
+            log::warn!(
+                "Update check failed ({}); retrying in {:?} ({}/{})",
+                err,
+                backoff,
+                attempt,
+                retries
+            );
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+    };
+
+    // A hung endpoint would otherwise keep the check waiting indefinitely
+    let timeout = settings
+        .check_timeout_secs
+        .map_or(DEFAULT_CHECK_TIMEOUT, Duration::from_secs);
+    let update = tokio::time::timeout(timeout, attempts)
+        .await
+        .map_err(|_| Error::Timeout(timeout.as_secs()))??;
+
+    if let Some(update) = update.as_ref().filter(|_| !allow_downgrade) {
+        if let (Ok(available), Ok(installed)) = (
+            parse_version(&update.version),
+            parse_version(&update.current_version),
+        ) {
+            if available.cmp_precedence(&installed).is_lt() {
+                return Err(Error::DowngradeNotAllowed {
+                    available: update.version.clone(),
+                    installed: update.current_version.clone(),
+                });
+            }
+        }
+    }
+
+    let update_metadata = update
+        .as_ref()
+        .map(|update| UpdateMetadata::new(update, settings.skipped_version.as_deref()));
+
+    Ok((update, update_metadata))
+}
+
 /// Check for updates on the specified channel
 ///
 /// # Arguments
 /// * `app` - Application handle
 /// * `pending_update` - State to store pending update
-/// * `channel` - Release channel to check ("stable" or "beta")
+/// * `channel` - Release channel to check ("stable", "beta", "nightly", "alpha" or a custom name).
+///   Defaults to the channel saved with set_channel; a channel passed here is saved in its place.
+/// * `endpoint_override` - Manifest URL to check instead of the channel's
+///   endpoint, for self-hosted releases. Tauri's `{{target}}`, `{{arch}}` and
+///   `{{current_version}}` placeholders are filled in.
+/// * `allow_downgrade` - Offer the channel's release even if it is older than the
+///   installed version (default false). Otherwise an older release fails with
+///   `Error::DowngradeNotAllowed`, and the pending update is cleared.
+/// * `version` - Offer this version instead of the channel's latest, newer or
+///   older than the installed one, using the manifest published with it; see
+///   list_available_versions. Can't be combined with `endpoint_override`.
 ///
 /// # Returns
-/// Update metadata if an update is available, None otherwise
+/// Update metadata if an update is available, None otherwise (for a target
+/// version, None means it is already installed)
 #[cfg(desktop)]
 #[tauri::command]
 pub async fn check_for_update(
     app: AppHandle,
     pending_update: State<'_, PendingUpdate>,
-    channel: String,
+    release_notes: State<'_, release_notes::ReleaseNotesCache>,
+    channel: Option<String>,
+    endpoint_override: Option<String>,
+    allow_downgrade: Option<bool>,
+    version: Option<String>,
 ) -> Result<Option<UpdateMetadata>> {
-    log::info!("Checking for updates on channel: {}", channel);
-
-    let release_channel = ReleaseChannel::from_str(&channel);
+    let mut settings = UpdaterSettings::load(&app);
+    let release_channel = match channel {
+        Some(channel) => {
+            let release_channel = parse_channel(&channel)?;
+            // Remembered for the background checks
+            if settings.channel.as_deref() != Some(release_channel.to_str()) {
+                settings.channel = Some(release_channel.to_str().to_string());
+                if let Err(err) = settings.save(&app) {
+                    log::warn!("Failed to save update channel: {}", err);
+                }
+            }
+            release_channel
+        }
+        None => settings.channel(),
+    };
 
-    // Build the updater with appropriate settings based on channel
-    let mut builder = app.updater_builder();
+    let target_version = version.as_deref().map(parse_version).transpose()?;
+    let (endpoint_override, allow_downgrade) = match &target_version {
+        Some(target) => {
+            if let Some(url) = endpoint_override.filter(|url| !url.trim().is_empty()) {
+                return Err(Error::InvalidEndpoint {
+                    url,
+                    reason: "an endpoint override can't be combined with a target version"
+                        .to_string(),
+                });
+            }
+            log::info!("Looking up the manifest for version {}", target);
+            let manifest = release_notes::version_manifest(&app, &release_notes, target).await?;
+            // Choosing an older version is the point of naming one
+            (Some(manifest), true)
+        }
+        None => (endpoint_override, allow_downgrade.unwrap_or(false)),
+    };
 
-    // For beta channel, check pre-releases
-    if release_channel == ReleaseChannel::Beta {
-        // GitHub releases with pre-release flag
-        builder = builder.endpoints(vec![
-            "https://github.com/yorphos/invariant/releases/download/latest-beta/latest.json"
-                .parse()
-                .expect("invalid beta URL"),
-        ])?;
+    let result = check(&app, &release_channel, endpoint_override, allow_downgrade).await;
+    if let Err(Error::DowngradeNotAllowed { .. }) = &result {
+        // A release from the previous channel shouldn't stay pending
+        *pending_update.0.lock().unwrap() = None;
     }
-    // Stable channel uses default endpoint from tauri.conf.json
+    let (update, mut update_metadata) = result?;
 
-    let update = builder.build()?.check().await?;
-
-    let update_metadata = update.as_ref().map(|update| UpdateMetadata {
-        version: update.version.clone(),
-        current_version: update.current_version.clone(),
-        date: update.date.as_ref().map(|d| d.to_string()),
-        body: update.body.clone(),
-    });
+    if let (Some(target), Some(update)) = (&target_version, &update) {
+        let matches = parse_version(&update.version)
+            .is_ok_and(|available| available.cmp_precedence(target).is_eq());
+        if !matches {
+            *pending_update.0.lock().unwrap() = None;
+            return Err(Error::InvalidManifest {
+                url: update.download_url.to_string(),
+                reason: format!(
+                    "the manifest for {} describes version {}",
+                    target, update.version
+                ),
+            });
+        }
+    }
+    if let Some(metadata) = update_metadata.as_mut().filter(|_| target_version.is_some()) {
+        // Asked for by name, so it isn't treated as skipped
+        metadata.skipped = false;
+    }
 
     *pending_update.0.lock().unwrap() = update;
 
@@ -151,62 +743,435 @@ pub async fn check_for_update(
     Ok(update_metadata)
 }
 
-/// Download and install the pending update
+/// The update waiting to be installed, if any, without checking the server
 ///
-/// # Arguments
-/// * `app` - Application handle
-/// * `pending_update` - State containing the pending update
-///
-/// # Returns
-/// Ok(()) on success, Error on failure
+/// Returns the pending update found by the last check, or if it was
+/// downloaded with download_update, the staged update; install them with
+/// download_and_install_update and install_staged_update respectively.
 #[cfg(desktop)]
 #[tauri::command]
-pub async fn download_and_install_update(
+pub fn has_pending_update(
     app: AppHandle,
     pending_update: State<'_, PendingUpdate>,
-) -> Result<()> {
-    log::info!("Starting update download and installation");
+    staged_update: State<'_, StagedUpdate>,
+) -> Option<UpdateMetadata> {
+    let skipped_version = UpdaterSettings::load(&app).skipped_version;
+    let metadata = |update: &Update| UpdateMetadata::new(update, skipped_version.as_deref());
+
+    if let Some(update) = pending_update.0.lock().unwrap().as_ref() {
+        return Some(metadata(update));
+    }
+    staged_update
+        .0
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|(update, _)| metadata(update))
+}
+
+/// Shortest time between two download Progress events
+const PROGRESS_EVENT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A Progress event for `length` new bytes, with speed averaged since `started_at`
+fn progress_event(
+    length: usize,
+    downloaded: u64,
+    total: Option<u64>,
+    resumed_from: u64,
+    started_at: Instant,
+) -> DownloadEvent {
+    // Only bytes fetched in this attempt count towards the speed
+    let elapsed = started_at.elapsed().as_secs_f64();
+    let bytes_per_second = if elapsed > 0.0 {
+        (downloaded - resumed_from) as f64 / elapsed
+    } else {
+        0.0
+    };
+    let eta_seconds = total
+        .filter(|_| bytes_per_second > 0.0)
+        .map(|total| total.saturating_sub(downloaded) as f64 / bytes_per_second);
+    DownloadEvent::Progress {
+        chunk_length: length,
+        downloaded,
+        total,
+        bytes_per_second,
+        eta_seconds,
+    }
+}
+
+/// Download the pending update and verify it, ready to install
+///
+/// If the download fails or is cancelled, the update stays pending so it can
+/// be retried, and the retry resumes from the bytes already downloaded.
+async fn download_pending(
+    app: &AppHandle,
+    pending_update: &PendingUpdate,
+    cancellation: &UpdateCancellation,
+) -> Result<(Update, Vec<u8>)> {
+    log::info!("Starting update download");
 
     let Some(update) = pending_update.0.lock().unwrap().take() else {
-        log::warn!("No pending update to install");
+        log::warn!("No pending update to download");
         return Err(Error::NoPendingUpdate);
     };
 
-    let mut started = false;
+    cancellation.requested.store(false, Ordering::SeqCst);
+    let mut started_at = Instant::now();
+    let mut resumed_from: u64 = 0;
+    // Chunks arrive far more often than the UI can use, so their lengths are
+    // added up between Progress events
+    let mut last_progress_event: Option<Instant> = None;
+    let mut unreported: usize = 0;
+    let mut position: (u64, Option<u64>) = (0, None);
 
-    update
-        .download_and_install(
-            |chunk_length, content_length| {
-                if !started {
-                    log::info!("Download started, content length: {:?}", content_length);
-                    let _ = app.emit("download-and-install-update", DownloadEvent::Started { content_length });
-                    started = true;
-                }
+    let download = download::download(app, &update, |progress| match progress {
+        download::Progress::Started {
+            resumed_from: offset,
+            total,
+        } => {
+            log::info!("Download started, content length: {:?}", total);
+            started_at = Instant::now();
+            resumed_from = offset;
+            let _ = app.emit(
+                "download-and-install-update",
+                DownloadEvent::Started {
+                    content_length: total,
+                    resumed_from,
+                },
+            );
+        }
+        download::Progress::Chunk {
+            length,
+            downloaded,
+            total,
+        } => {
+            unreported += length;
+            position = (downloaded, total);
+            if cancellation.is_requested() {
+                return;
+            }
+
+            let due = last_progress_event
+                .map_or(true, |emitted| emitted.elapsed() >= PROGRESS_EVENT_INTERVAL);
+            if !due && Some(downloaded) != total {
+                return;
+            }
+            let _ = app.emit(
+                "download-and-install-update",
+                progress_event(unreported, downloaded, total, resumed_from, started_at),
+            );
+            unreported = 0;
+            last_progress_event = Some(Instant::now());
+        }
+        download::Progress::Verifying => {
+            // Without a known size the last chunk may not have been reported
+            if unreported > 0 {
+                let (downloaded, total) = position;
+                let _ = app.emit(
+                    "download-and-install-update",
+                    progress_event(unreported, downloaded, total, resumed_from, started_at),
+                );
+            }
+            log::info!("Download finished, verifying...");
+            let _ = app.emit("download-and-install-update", DownloadEvent::Verifying);
+        }
+    });
+
+    // Dropping the download future stops it reading the response body
+    let result = tokio::select! {
+        result = download => result,
+        _ = cancellation.requested() => Err(Error::Cancelled),
+    };
+
+    let bytes = match result {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            if matches!(err, Error::Cancelled) {
+                log::info!("Update download cancelled");
+                let _ = app.emit("download-and-install-update", DownloadEvent::Cancelled);
+            }
+            *pending_update.0.lock().unwrap() = Some(update);
+            return Err(err);
+        }
+    };
+
+    log::info!("Update verified");
+    let _ = app.emit("download-and-install-update", DownloadEvent::Finished);
+
+    Ok((update, bytes))
+}
+
+/// Warn the frontend that the app is about to close, giving it time to save its state
+///
+/// Emits `update-restarting`, then waits until the frontend emits
+/// `update-restart-ready` or the grace period runs out.
+async fn announce_restart(app: &AppHandle, version: &str) {
+    let (ready_tx, ready_rx) = tokio::sync::oneshot::channel();
+    let listener = app.once(UPDATE_RESTART_READY_EVENT, move |_| {
+        let _ = ready_tx.send(());
+    });
 
-                let _ = app.emit("download-and-install-update", DownloadEvent::Progress { chunk_length });
-            },
-            || {
-                log::info!("Download finished, installing...");
-                let _ = app.emit("download-and-install-update", DownloadEvent::Finished);
-            },
-        )
-        .await?;
+    let _ = app.emit(
+        UPDATE_RESTARTING_EVENT,
+        UpdateRestarting {
+            version: version.to_string(),
+            grace_period_ms: RESTART_GRACE_PERIOD.as_millis() as u64,
+        },
+    );
+
+    if tokio::time::timeout(RESTART_GRACE_PERIOD, ready_rx)
+        .await
+        .is_err()
+    {
+        log::info!("Frontend didn't confirm it was ready, restarting anyway");
+        app.unlisten(listener);
+    }
+}
+
+/// Install a downloaded update, then restart into it unless `restart_after` is false
+///
+/// `update-restarting` is emitted before the app closes: on Windows before
+/// the installer starts, since it closes the app itself, and elsewhere
+/// before the restart.
+async fn install(
+    app: &AppHandle,
+    update: Update,
+    bytes: Vec<u8>,
+    restart_after: Option<bool>,
+) -> Result<()> {
+    #[cfg(target_os = "windows")]
+    announce_restart(app, &update.version).await;
+
+    log::info!("Installing update {}", update.version);
+    update.install(bytes)?;
 
     log::info!("Update installed successfully");
 
     // On Windows, the app will exit automatically
     // On macOS/Linux, we need to restart manually
     #[cfg(not(target_os = "windows"))]
-    {
+    if restart_after.unwrap_or(true) {
+        announce_restart(app, &update.version).await;
         log::info!("Restarting application...");
         app.restart();
-        // restart() doesn't return, but we need this for Windows path
+    } else {
+        log::info!("Update will apply on next launch");
+        crate::notifications::notify(
+            app,
+            "Update installed",
+            &format!(
+                "Invariant {} starts the next time you open the app",
+                update.version
+            ),
+        );
     }
 
     #[cfg(target_os = "windows")]
+    let _ = (app, restart_after);
+
     Ok(())
 }
 
+/// Download and install the pending update
+///
+/// # Arguments
+/// * `app` - Application handle
+/// * `pending_update` - State containing the pending update
+/// * `cancellation` - Flag set by cancel_update
+/// * `restart_after` - Restart into the new version once installed (default true).
+///   When false the update is installed but the running app carries on, and the
+///   new version starts the next time the app is launched or restart_app is called.
+///   On Windows the installer always closes the app.
+///
+/// # Returns
+/// Ok(()) on success, Error on failure. If the download fails or is
+/// cancelled, the update stays pending so it can be retried, and the retry
+/// resumes from the bytes already downloaded.
+#[cfg(desktop)]
+#[tauri::command]
+pub async fn download_and_install_update(
+    app: AppHandle,
+    pending_update: State<'_, PendingUpdate>,
+    cancellation: State<'_, UpdateCancellation>,
+    restart_after: Option<bool>,
+) -> Result<()> {
+    let (update, bytes) = download_pending(&app, &pending_update, &cancellation).await?;
+    install(&app, update, bytes, restart_after).await
+}
+
+/// Download and verify the pending update without installing it
+///
+/// Emits the same progress events as download_and_install_update. The
+/// verified package is kept until install_staged_update installs it, or a
+/// newer download replaces it.
+#[cfg(desktop)]
+#[tauri::command]
+pub async fn download_update(
+    app: AppHandle,
+    pending_update: State<'_, PendingUpdate>,
+    staged_update: State<'_, StagedUpdate>,
+    cancellation: State<'_, UpdateCancellation>,
+) -> Result<UpdateMetadata> {
+    let (update, bytes) = download_pending(&app, &pending_update, &cancellation).await?;
+
+    let skipped_version = UpdaterSettings::load(&app).skipped_version;
+    let metadata = UpdateMetadata::new(&update, skipped_version.as_deref());
+
+    log::info!("Update {} staged for installation", update.version);
+    crate::notifications::notify(
+        &app,
+        "Update ready to install",
+        &format!("Invariant {} has been downloaded", update.version),
+    );
+    *staged_update.0.lock().unwrap() = Some((update, bytes));
+
+    Ok(metadata)
+}
+
+/// Install the update staged by download_update
+///
+/// # Arguments
+/// * `restart_after` - As for download_and_install_update (default true)
+#[cfg(desktop)]
+#[tauri::command]
+pub async fn install_staged_update(
+    app: AppHandle,
+    staged_update: State<'_, StagedUpdate>,
+    restart_after: Option<bool>,
+) -> Result<()> {
+    let Some((update, bytes)) = staged_update.0.lock().unwrap().take() else {
+        log::warn!("No staged update to install");
+        return Err(Error::NoStagedUpdate);
+    };
+
+    install(&app, update, bytes, restart_after).await
+}
+
+/// Restart the application, starting an update installed with `restart_after: false`
+#[cfg(desktop)]
+#[tauri::command]
+pub fn restart_app(app: AppHandle) {
+    log::info!("Restarting application...");
+    app.restart();
+}
+
+/// Stop offering updates up to and including `version`
+///
+/// check_for_update still returns such updates, marked `skipped`, so a
+/// manual check can offer them; a newer release is not skipped.
+#[cfg(desktop)]
+#[tauri::command]
+pub fn skip_version(app: AppHandle, version: String) -> Result<()> {
+    let version = parse_version(&version)?.to_string();
+    log::info!("Skipping updates up to version {}", version);
+
+    let mut settings = UpdaterSettings::load(&app);
+    settings.skipped_version = Some(version);
+    settings.save(&app)
+}
+
+/// Get the release channel saved with set_channel ("stable" if none was chosen)
+#[cfg(desktop)]
+#[tauri::command]
+pub fn get_current_channel(app: AppHandle) -> String {
+    UpdaterSettings::load(&app).channel().to_str().to_string()
+}
+
+/// Choose the release channel that update checks use by default
+///
+/// The channel is saved, so it lasts across restarts; background checks use it too.
+#[cfg(desktop)]
+#[tauri::command]
+pub fn set_channel(app: AppHandle, channel: String) -> Result<()> {
+    let release_channel = parse_channel(&channel)?;
+    log::info!("Update channel set to {}", release_channel.to_str());
+
+    let mut settings = UpdaterSettings::load(&app);
+    settings.channel = Some(release_channel.to_str().to_string());
+    settings.save(&app)
+}
+
+/// Set the fallback manifest URLs for a release channel
+///
+/// Checks on the channel try its usual endpoint first, then each mirror in
+/// order until one responds with a valid manifest. Pass an empty list to
+/// remove the mirrors. They are saved, so they last across restarts.
+#[cfg(desktop)]
+#[tauri::command]
+pub fn set_update_mirrors(app: AppHandle, channel: String, mirrors: Vec<String>) -> Result<()> {
+    let release_channel = parse_channel(&channel)?;
+    let mirrors: Vec<String> = mirrors
+        .iter()
+        .map(|url| url.trim())
+        .filter(|url| !url.is_empty())
+        .map(|url| parse_endpoint(url).map(|_| url.to_string()))
+        .collect::<Result<_>>()?;
+
+    let mut settings = UpdaterSettings::load(&app);
+    if mirrors.is_empty() {
+        settings.mirrors.remove(release_channel.to_str());
+    } else {
+        settings
+            .mirrors
+            .insert(release_channel.to_str().to_string(), mirrors);
+    }
+    settings.save(&app)
+}
+
+/// Set the proxy used to check for and download updates
+///
+/// Pass None to go back to the HTTP_PROXY/HTTPS_PROXY environment variables
+/// and system proxy settings. The proxy is saved, so it lasts across restarts.
+#[cfg(desktop)]
+#[tauri::command]
+pub fn set_update_proxy(app: AppHandle, proxy: Option<String>) -> Result<()> {
+    let proxy = proxy.filter(|proxy| !proxy.trim().is_empty());
+    if let Some(proxy) = proxy.as_deref() {
+        parse_proxy(proxy)?;
+    }
+
+    let mut settings = UpdaterSettings::load(&app);
+    settings.proxy = proxy.map(|proxy| proxy.trim().to_string());
+    settings.save(&app)
+}
+
+/// Cancel the update download started by download_and_install_update
+///
+/// Does nothing if no download is in progress. Once the download has
+/// finished the update is installed regardless.
+#[cfg(desktop)]
+#[tauri::command]
+pub fn cancel_update(cancellation: State<'_, UpdateCancellation>) {
+    log::info!("Cancelling update download");
+    cancellation.requested.store(true, Ordering::SeqCst);
+    cancellation.notify.notify_waiters();
+}
+
+/// Compare two semantic versions
+///
+/// # Returns
+/// -1 if `a` is older than `b`, 0 if they are the same version, 1 if `a` is newer.
+/// Pre-releases sort before the release (`1.2.0-beta.1` < `1.2.0`) and build
+/// metadata is ignored.
+#[cfg(desktop)]
+#[tauri::command]
+pub fn compare_versions(a: String, b: String) -> Result<i32> {
+    let a = parse_version(&a)?;
+    let b = parse_version(&b)?;
+    Ok(match a.cmp_precedence(&b) {
+        std::cmp::Ordering::Less => -1,
+        std::cmp::Ordering::Equal => 0,
+        std::cmp::Ordering::Greater => 1,
+    })
+}
+
+/// Whether `candidate` is a newer version than `current`
+#[cfg(desktop)]
+#[tauri::command]
+pub fn is_newer(candidate: String, current: String) -> Result<bool> {
+    Ok(compare_versions(candidate, current)? > 0)
+}
+
 /// Get the current application version
 #[cfg(desktop)]
 #[tauri::command]
@@ -0,0 +1,263 @@
+//! Resumable update downloads
+//!
+//! The updater plugin downloads the whole package into memory, so a dropped
+//! connection throws away everything received so far. This downloads it into
+//! a partial file in the app cache directory instead and, on the next
+//! attempt, asks the server for only the remaining bytes with a `Range`
+//! request. Servers that ignore `Range` get a fresh download.
+//!
+//! Because the plugin's own download is bypassed, the package signature is
+//! verified here before it is handed to `Update::install`, along with the
+//! SHA-256 checksum if the manifest lists one.
+
+use std::path::PathBuf;
+
+use base64::Engine;
+use futures_util::StreamExt;
+use minisign_verify::{PublicKey, Signature};
+use reqwest::header::{HeaderValue, ACCEPT, CONTENT_RANGE, RANGE};
+use reqwest::StatusCode;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_updater::Update;
+use tokio::io::AsyncWriteExt;
+
+use super::{expected_checksum, http_client, Error, Result};
+
+/// Reported to the caller of [`download`] as the package arrives
+pub(super) enum Progress {
+    /// The server responded; `resumed_from` bytes were already on disk
+    Started {
+        resumed_from: u64,
+        total: Option<u64>,
+    },
+    /// A chunk was written; `downloaded` includes the resumed bytes
+    Chunk {
+        length: usize,
+        downloaded: u64,
+        total: Option<u64>,
+    },
+    /// Everything has arrived and the package is being verified
+    Verifying,
+}
+
+/// Where a partially downloaded package is kept between attempts
+struct PartialDownload {
+    /// The bytes received so far
+    data: PathBuf,
+    /// The URL they came from, so a re-published package starts over
+    source: PathBuf,
+}
+
+impl PartialDownload {
+    fn new(app: &AppHandle, update: &Update) -> Result<Self> {
+        let dir = app
+            .path()
+            .app_cache_dir()
+            .map_err(|e| Error::Download(e.to_string()))?
+            .join("updates");
+        std::fs::create_dir_all(&dir).map_err(|e| Error::Download(e.to_string()))?;
+
+        let name = format!("{}-{}", update.version, update.target);
+        Ok(Self {
+            data: dir.join(format!("{}.part", name)),
+            source: dir.join(format!("{}.url", name)),
+        })
+    }
+
+    /// Bytes already downloaded from `url`, discarding anything from elsewhere
+    async fn resume_offset(&self, url: &str) -> u64 {
+        let same_source = tokio::fs::read_to_string(&self.source)
+            .await
+            .is_ok_and(|source| source == url);
+        if !same_source {
+            self.remove().await;
+            return 0;
+        }
+
+        tokio::fs::metadata(&self.data)
+            .await
+            .map_or(0, |metadata| metadata.len())
+    }
+
+    async fn remove(&self) {
+        let _ = tokio::fs::remove_file(&self.data).await;
+        let _ = tokio::fs::remove_file(&self.source).await;
+    }
+}
+
+/// The total size from a `Content-Range: bytes start-end/total` header
+fn content_range_total(value: &HeaderValue) -> Option<u64> {
+    value.to_str().ok()?.rsplit('/').next()?.parse().ok()
+}
+
+fn client(app: &AppHandle, update: &Update) -> Result<reqwest::Client> {
+    let proxy = update.proxy.as_ref().filter(|_| !update.no_proxy);
+    let mut builder = http_client(app, proxy).map_err(|e| Error::Download(e.to_string()))?;
+    if let Some(timeout) = update.timeout {
+        builder = builder.timeout(timeout);
+    }
+    if update.no_proxy {
+        builder = builder.no_proxy();
+    }
+
+    builder.build().map_err(|e| Error::Download(e.to_string()))
+}
+
+/// Check a downloaded package against the update's minisign signature
+fn verify_signature(app: &AppHandle, update: &Update, data: &[u8]) -> Result<()> {
+    let pubkey = app
+        .config()
+        .plugins
+        .0
+        .get("updater")
+        .and_then(|updater| updater.get("pubkey"))
+        .and_then(|pubkey| pubkey.as_str())
+        .ok_or_else(|| Error::Updater("no updater pubkey is configured".to_string()))?;
+
+    let decode = |value: &str| -> Result<String> {
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(value)
+            .map_err(|e| Error::Updater(format!("invalid signature encoding: {}", e)))?;
+        String::from_utf8(bytes)
+            .map_err(|e| Error::Updater(format!("invalid signature encoding: {}", e)))
+    };
+
+    let public_key = PublicKey::decode(&decode(pubkey)?)
+        .map_err(|e| Error::Updater(format!("invalid updater pubkey: {}", e)))?;
+    let signature = Signature::decode(&decode(&update.signature)?)
+        .map_err(|e| Error::Updater(format!("invalid update signature: {}", e)))?;
+
+    public_key
+        .verify(data, &signature, true)
+        .map_err(|e| Error::Updater(format!("update signature verification failed: {}", e)))
+}
+
+/// Check a downloaded package against the SHA-256 in the manifest, if there is one
+fn verify_checksum(update: &Update, data: &[u8]) -> Result<()> {
+    let Some(expected) = expected_checksum(update) else {
+        return Ok(());
+    };
+
+    let actual = format!("{:x}", Sha256::digest(data));
+    if actual != expected {
+        return Err(Error::ChecksumMismatch { expected, actual });
+    }
+
+    Ok(())
+}
+
+/// Download an update package, resuming a previous partial download if there is one
+///
+/// The partial file is kept if the download fails or the future is dropped,
+/// and removed once the package is complete and verified.
+pub(super) async fn download<F: FnMut(Progress)>(
+    app: &AppHandle,
+    update: &Update,
+    mut on_progress: F,
+) -> Result<Vec<u8>> {
+    let partial = PartialDownload::new(app, update)?;
+    let url = update.download_url.as_str();
+    let client = client(app, update)?;
+
+    let mut offset = partial.resume_offset(url).await;
+    let response = loop {
+        let mut request = client
+            .get(update.download_url.clone())
+            .headers(update.headers.clone());
+        if !update.headers.contains_key(ACCEPT) {
+            request = request.header(ACCEPT, "application/octet-stream");
+        }
+        if offset > 0 {
+            request = request.header(RANGE, format!("bytes={}-", offset));
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| Error::Download(e.to_string()))?;
+
+        // The partial file is no use for this package; start over
+        if response.status() == StatusCode::RANGE_NOT_SATISFIABLE && offset > 0 {
+            log::warn!("Server rejected resuming the update download; restarting it");
+            partial.remove().await;
+            offset = 0;
+            continue;
+        }
+
+        break response;
+    };
+
+    if !response.status().is_success() {
+        return Err(Error::Download(format!(
+            "download request failed with status: {}",
+            response.status()
+        )));
+    }
+
+    let resumed = response.status() == StatusCode::PARTIAL_CONTENT && offset > 0;
+    let total = if resumed {
+        response
+            .headers()
+            .get(CONTENT_RANGE)
+            .and_then(content_range_total)
+            .or_else(|| response.content_length().map(|length| length + offset))
+    } else {
+        if offset > 0 {
+            log::info!("Server doesn't support resuming downloads; restarting the update download");
+        }
+        offset = 0;
+        response.content_length()
+    };
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(&partial.data)
+        .await
+        .map_err(|e| Error::Download(e.to_string()))?;
+    tokio::fs::write(&partial.source, url)
+        .await
+        .map_err(|e| Error::Download(e.to_string()))?;
+
+    if resumed {
+        log::info!("Resuming update download at byte {}", offset);
+    }
+    on_progress(Progress::Started {
+        resumed_from: offset,
+        total,
+    });
+
+    let mut downloaded = offset;
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| Error::Download(e.to_string()))?;
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| Error::Download(e.to_string()))?;
+        downloaded += chunk.len() as u64;
+        on_progress(Progress::Chunk {
+            length: chunk.len(),
+            downloaded,
+            total,
+        });
+    }
+    file.flush()
+        .await
+        .map_err(|e| Error::Download(e.to_string()))?;
+    drop(file);
+
+    let data = tokio::fs::read(&partial.data)
+        .await
+        .map_err(|e| Error::Download(e.to_string()))?;
+    // Whatever is on disk can't become valid by appending more, so a bad package is discarded
+    on_progress(Progress::Verifying);
+    let verified =
+        verify_signature(app, update, &data).and_then(|()| verify_checksum(update, &data));
+    partial.remove().await;
+    verified?;
+
+    Ok(data)
+}
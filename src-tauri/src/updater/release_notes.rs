@@ -0,0 +1,255 @@
+//! Release notes for a range of versions, and the versions available to install
+//!
+//! The update manifest only describes the newest release, so notes for every
+//! release between two versions come from the GitHub releases API instead.
+//! The same list tells which older versions can be installed: each release
+//! carries its own `latest.json` manifest, which check_for_update can point
+//! the updater at. The release list is cached for a few minutes so that
+//! opening the changelog screen repeatedly doesn't run into GitHub's rate
+//! limit.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+
+use super::settings::UpdaterSettings;
+use super::{http_client, parse_proxy, parse_version, release_repo, Error, ReleaseChannel, Result};
+
+/// Name of the update manifest attached to each release
+const MANIFEST_ASSET: &str = "latest.json";
+
+/// How long a fetched release list is reused
+const CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Release notes for one version
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReleaseNote {
+    pub version: String,
+    pub date: Option<String>,
+    pub body: Option<String>,
+}
+
+/// A release as returned by the GitHub API
+#[derive(Debug, Clone, Deserialize)]
+struct GithubRelease {
+    tag_name: String,
+    body: Option<String>,
+    published_at: Option<String>,
+    draft: bool,
+    #[serde(default)]
+    assets: Vec<GithubAsset>,
+}
+
+/// A file attached to a release
+#[derive(Debug, Clone, Deserialize)]
+struct GithubAsset {
+    name: String,
+    browser_download_url: String,
+}
+
+impl GithubRelease {
+    /// The published version; None for drafts and for moving tags such as
+    /// latest-beta, which aren't versions
+    fn version(&self) -> Option<Version> {
+        if self.draft {
+            return None;
+        }
+        parse_version(&self.tag_name).ok()
+    }
+
+    /// URL of the release's update manifest, if it has one
+    fn manifest_url(&self) -> Option<&str> {
+        self.assets
+            .iter()
+            .find(|asset| asset.name == MANIFEST_ASSET)
+            .map(|asset| asset.browser_download_url.as_str())
+    }
+}
+
+/// Releases fetched from GitHub, shared by every channel
+#[derive(Default)]
+pub struct ReleaseNotesCache(Mutex<Option<(Instant, Vec<GithubRelease>)>>);
+
+impl ReleaseNotesCache {
+    fn get(&self) -> Option<Vec<GithubRelease>> {
+        let cached = self.0.lock().unwrap();
+        cached
+            .as_ref()
+            .filter(|(fetched_at, _)| fetched_at.elapsed() < CACHE_TTL)
+            .map(|(_, releases)| releases.clone())
+    }
+
+    fn set(&self, releases: Vec<GithubRelease>) {
+        *self.0.lock().unwrap() = Some((Instant::now(), releases));
+    }
+}
+
+/// Whether a version is published on a channel
+///
+/// Stable only sees full releases; other channels also see their own
+/// pre-releases, e.g. `1.4.0-beta.2` on beta.
+fn on_channel(version: &Version, channel: &ReleaseChannel) -> bool {
+    if version.pre.is_empty() {
+        return true;
+    }
+
+    match channel {
+        ReleaseChannel::Stable => false,
+        channel => version
+            .pre
+            .as_str()
+            .split('.')
+            .next()
+            .is_some_and(|tag| tag.eq_ignore_ascii_case(channel.to_str())),
+    }
+}
+
+async fn fetch_releases(app: &AppHandle) -> Result<Vec<GithubRelease>> {
+    let proxy = UpdaterSettings::load(app)
+        .proxy
+        .as_deref()
+        .map(parse_proxy)
+        .transpose()?;
+    let client = http_client(app, proxy.as_ref())
+        .and_then(|builder| builder.timeout(REQUEST_TIMEOUT).build())
+        .map_err(|e| Error::Releases(e.to_string()))?;
+
+    // Most recent releases first; only the first page is fetched
+    let url = format!(
+        "https://api.github.com/repos/{}/releases?per_page=100",
+        release_repo(app)
+    );
+    client
+        .get(url)
+        .header("Accept", "application/vnd.github+json")
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| Error::Releases(e.to_string()))?
+        .json::<Vec<GithubRelease>>()
+        .await
+        .map_err(|e| Error::Releases(e.to_string()))
+}
+
+/// The release list, from the cache if it was fetched recently
+async fn releases(app: &AppHandle, cache: &ReleaseNotesCache) -> Result<Vec<GithubRelease>> {
+    if let Some(releases) = cache.get() {
+        return Ok(releases);
+    }
+
+    log::info!("Fetching releases from GitHub");
+    let releases = fetch_releases(app).await?;
+    cache.set(releases.clone());
+    Ok(releases)
+}
+
+/// URL of the update manifest published with `version`
+///
+/// Fails with `Error::VersionNotFound` if there is no such release, or it
+/// was published without a manifest and so can't be installed by the updater.
+pub(super) async fn version_manifest(
+    app: &AppHandle,
+    cache: &ReleaseNotesCache,
+    version: &Version,
+) -> Result<String> {
+    releases(app, cache)
+        .await?
+        .iter()
+        .filter(|release| {
+            release
+                .version()
+                .is_some_and(|published| published.cmp_precedence(version).is_eq())
+        })
+        .find_map(|release| release.manifest_url().map(str::to_string))
+        .ok_or_else(|| Error::VersionNotFound(version.to_string()))
+}
+
+/// List the versions published on a channel that the updater can install
+///
+/// Includes versions older than the one running, for rolling back or
+/// reproducing a bug in a specific release; install one by passing it to
+/// check_for_update as `version`. Only the 100 most recent releases are
+/// considered.
+///
+/// # Arguments
+/// * `channel` - Release channel whose pre-releases to include
+///
+/// # Returns
+/// Version strings, newest first
+#[tauri::command]
+pub async fn list_available_versions(
+    app: AppHandle,
+    cache: State<'_, ReleaseNotesCache>,
+    channel: String,
+) -> Result<Vec<String>> {
+    let channel = ReleaseChannel::from_str(&channel);
+
+    let mut versions: Vec<Version> = releases(&app, &cache)
+        .await?
+        .iter()
+        .filter(|release| release.manifest_url().is_some())
+        .filter_map(GithubRelease::version)
+        .filter(|version| on_channel(version, &channel))
+        .collect();
+
+    versions.sort_by(|a, b| b.cmp_precedence(a));
+    versions.dedup_by(|a, b| a.cmp_precedence(b).is_eq());
+
+    Ok(versions.iter().map(Version::to_string).collect())
+}
+
+/// Get the notes for every release after `from_version` up to and including `to_version`
+///
+/// Versions are compared by semver precedence, so build metadata is ignored.
+/// Only the 100 most recent releases are searched.
+///
+/// # Arguments
+/// * `app` - Application handle
+/// * `cache` - Recently fetched releases
+/// * `from_version` - Version the user is running; its own notes are not included
+/// * `to_version` - Newest version to include
+/// * `channel` - Release channel whose pre-releases to include
+///
+/// # Returns
+/// Release notes, newest first
+#[tauri::command]
+pub async fn get_release_notes(
+    app: AppHandle,
+    cache: State<'_, ReleaseNotesCache>,
+    from_version: String,
+    to_version: String,
+    channel: String,
+) -> Result<Vec<ReleaseNote>> {
+    let from = parse_version(&from_version)?;
+    let to = parse_version(&to_version)?;
+    let channel = ReleaseChannel::from_str(&channel);
+
+    let mut notes: Vec<(Version, ReleaseNote)> = releases(&app, &cache)
+        .await?
+        .into_iter()
+        .filter_map(|release| {
+            let version = release.version()?;
+            let note = ReleaseNote {
+                version: version.to_string(),
+                date: release.published_at,
+                body: release.body,
+            };
+            Some((version, note))
+        })
+        .filter(|(version, _)| {
+            version.cmp_precedence(&from).is_gt()
+                && version.cmp_precedence(&to).is_le()
+                && on_channel(version, &channel)
+        })
+        .collect();
+
+    notes.sort_by(|(a, _), (b, _)| b.cmp_precedence(a));
+
+    Ok(notes.into_iter().map(|(_, note)| note).collect())
+}
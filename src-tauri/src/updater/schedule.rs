@@ -0,0 +1,145 @@
+//! Background update checks
+//!
+//! When enabled, a task checks for updates on a fixed interval and, if a new
+//! version is found, stores it as the pending update and emits
+//! `update-available` with its [`UpdateMetadata`](super::UpdateMetadata).
+//! Nothing is downloaded; the frontend decides whether to offer the update.
+//!
+//! At most one task runs at a time: [`Scheduler`] holds it, and starting the
+//! checks aborts the task already running before spawning its replacement.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use super::settings::UpdaterSettings;
+use super::{check, Error, PendingUpdate, Result};
+
+/// Event emitted with the update's metadata when a background check finds an update
+pub const UPDATE_AVAILABLE_EVENT: &str = "update-available";
+
+/// Shortest interval allowed, so a typo can't hammer the update server
+const MIN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Interval used when auto-update is turned on without `checkIntervalMinutes` set
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// The background check task, while one is running
+#[derive(Default)]
+pub struct Scheduler(Mutex<Option<JoinHandle<()>>>);
+
+/// How often to check in the background, or None if the checks are off
+///
+/// The checks are on when `checkIntervalMinutes` is set in the updater
+/// settings file, unless set_auto_update_enabled turned them off.
+fn interval(settings: &UpdaterSettings) -> Option<Duration> {
+    let configured = settings
+        .check_interval_minutes
+        .map(|minutes| Duration::from_secs(minutes.saturating_mul(60)));
+    match settings.auto_update {
+        Some(false) => None,
+        Some(true) => Some(configured.unwrap_or(DEFAULT_INTERVAL)),
+        None => configured,
+    }
+}
+
+/// Start the background checks if they are enabled
+///
+/// Each check uses the channel chosen with set_channel and stays quiet about
+/// skipped versions. The first check runs one interval after the checks
+/// start, since the frontend already checks on launch. A task that was
+/// already running is stopped first.
+pub fn start(app: &AppHandle) {
+    let scheduler = app.state::<Scheduler>();
+    let mut task = scheduler.0.lock().unwrap();
+    if let Some(task) = task.take() {
+        task.abort();
+    }
+
+    let Some(check_interval) = interval(&UpdaterSettings::load(app)) else {
+        return;
+    };
+    let check_interval = check_interval.max(MIN_INTERVAL);
+
+    log::info!(
+        "Checking for updates in the background every {} minutes",
+        check_interval.as_secs() / 60
+    );
+
+    let app = app.clone();
+    *task = Some(tauri::async_runtime::spawn(async move {
+        let mut ticker = tokio::time::interval(check_interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        // The first tick completes immediately
+        ticker.tick().await;
+
+        loop {
+            ticker.tick().await;
+            check_in_background(&app).await;
+        }
+    }));
+}
+
+/// Stop the background checks, including one in progress
+fn stop(scheduler: &Scheduler) {
+    if let Some(task) = scheduler.0.lock().unwrap().take() {
+        task.abort();
+        log::info!("Stopped background update checks");
+    }
+}
+
+/// Whether background update checks are on
+#[tauri::command]
+pub fn get_auto_update_enabled(app: AppHandle) -> bool {
+    interval(&UpdaterSettings::load(&app)).is_some()
+}
+
+/// Turn background update checks on or off, now and for future launches
+///
+/// Turning them on checks every `checkIntervalMinutes` from the updater
+/// settings file, or every 6 hours if that isn't set. Turning them off stops
+/// the task, so no further checks reach the update server; checks started
+/// with check_for_update still work.
+#[tauri::command]
+pub fn set_auto_update_enabled(
+    app: AppHandle,
+    enabled: bool,
+    scheduler: State<'_, Scheduler>,
+) -> Result<()> {
+    let mut settings = UpdaterSettings::load(&app);
+    settings.auto_update = Some(enabled);
+    settings.save(&app)?;
+
+    if enabled {
+        start(&app);
+    } else {
+        stop(&scheduler);
+    }
+    Ok(())
+}
+
+async fn check_in_background(app: &AppHandle) {
+    let channel = UpdaterSettings::load(app).channel();
+
+    let (update, metadata) = match check(app, &channel, None, false).await {
+        Ok(result) => result,
+        Err(err @ Error::DowngradeNotAllowed { .. }) => {
+            log::info!("Background update check skipped a release: {}", err);
+            return;
+        }
+        Err(err) => {
+            log::warn!("Background update check failed: {}", err);
+            return;
+        }
+    };
+
+    let Some(metadata) = metadata.filter(|metadata| !metadata.skipped) else {
+        return;
+    };
+
+    log::info!("Background update check found version {}", metadata.version);
+    *app.state::<PendingUpdate>().0.lock().unwrap() = update;
+    let _ = app.emit(UPDATE_AVAILABLE_EVENT, metadata);
+}
@@ -0,0 +1,67 @@
+//! Updater preferences stored in the app config directory
+//!
+//! These live in their own small JSON file rather than the app database so
+//! the updater keeps working even when the database fails to open, which is
+//! exactly when an update is most needed.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use super::{Error, ReleaseChannel, Result};
+use crate::config;
+
+const SETTINGS_FILE: &str = "updater.json";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct UpdaterSettings {
+    /// Channel chosen with set_channel or used by the last update check
+    pub channel: Option<String>,
+    /// Updates up to and including this version are marked as skipped
+    pub skipped_version: Option<String>,
+    /// Check for updates in the background this often; off when unset, unless auto_update is on
+    pub check_interval_minutes: Option<u64>,
+    /// Run the background checks, chosen with set_auto_update_enabled; when
+    /// unset they run if check_interval_minutes is set
+    pub auto_update: Option<bool>,
+    /// How many times to retry an update check that couldn't reach the server
+    pub check_retries: Option<u32>,
+    /// Delay before the first retry, doubled for each one after
+    pub retry_backoff_ms: Option<u64>,
+    /// Give up on an update check, including its retries, after this long
+    pub check_timeout_secs: Option<u64>,
+    /// Proxy for update requests, instead of HTTP_PROXY/HTTPS_PROXY and the system settings
+    pub proxy: Option<String>,
+    /// Fallback manifest URLs by channel name, tried in order after the channel's endpoint
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub mirrors: BTreeMap<String, Vec<String>>,
+}
+
+impl UpdaterSettings {
+    /// Read the settings, falling back to defaults if the file is missing or unreadable
+    pub fn load(app: &AppHandle) -> Self {
+        config::load(app, SETTINGS_FILE)
+    }
+
+    /// The chosen release channel, stable if none has been chosen
+    pub fn channel(&self) -> ReleaseChannel {
+        self.channel
+            .as_deref()
+            .map_or(ReleaseChannel::Stable, ReleaseChannel::from_str)
+    }
+
+    /// The mirrors saved for a channel, in the order to try them
+    pub fn mirrors(&self, channel: &ReleaseChannel) -> impl Iterator<Item = &str> {
+        self.mirrors
+            .get(channel.to_str())
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+    }
+
+    pub fn save(&self, app: &AppHandle) -> Result<()> {
+        config::save(app, SETTINGS_FILE, self).map_err(|e| Error::Settings(e.to_string()))
+    }
+}
@@ -0,0 +1,81 @@
+//! Main window helpers
+//!
+//! The window's size, position and maximized state are saved on exit and
+//! restored at startup by tauri-plugin-window-state. The plugin skips a saved
+//! position that is entirely off-screen, but a window saved half on a monitor
+//! that has since been unplugged (or at a size larger than the current screen)
+//! would still open partly out of reach, so [`keep_on_screen`] pulls it back.
+
+use tauri::{AppHandle, Manager, Monitor, PhysicalPosition, PhysicalSize, WebviewWindow};
+
+/// Label of the window defined in tauri.conf.json
+pub const MAIN_WINDOW: &str = "main";
+
+/// Bring the main window to the front
+pub fn focus_main(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window(MAIN_WINDOW) {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+/// Area of `monitor`'s work area covered by a window
+fn overlap(monitor: &Monitor, position: PhysicalPosition<i32>, size: PhysicalSize<u32>) -> i64 {
+    let area = monitor.work_area();
+    let left = position.x.max(area.position.x) as i64;
+    let top = position.y.max(area.position.y) as i64;
+    let right = (position.x as i64 + size.width as i64)
+        .min(area.position.x as i64 + area.size.width as i64);
+    let bottom = (position.y as i64 + size.height as i64)
+        .min(area.position.y as i64 + area.size.height as i64);
+
+    (right - left).max(0) * (bottom - top).max(0)
+}
+
+/// Move and shrink a window as needed so it fits inside one monitor's work area
+///
+/// The monitor the window mostly covers is used, or the primary monitor if it
+/// isn't on any of them.
+pub fn keep_on_screen(window: &WebviewWindow) -> tauri::Result<()> {
+    if window.is_maximized()? || window.is_fullscreen()? {
+        return Ok(());
+    }
+
+    let position = window.outer_position()?;
+    let size = window.outer_size()?;
+
+    let monitors = window.available_monitors()?;
+    let target = monitors
+        .iter()
+        .map(|monitor| (overlap(monitor, position, size), monitor))
+        .filter(|(covered, _)| *covered > 0)
+        .max_by_key(|(covered, _)| *covered)
+        .map(|(_, monitor)| monitor.clone());
+    let Some(monitor) = target.or(window.primary_monitor()?) else {
+        return Ok(());
+    };
+
+    let area = monitor.work_area();
+    let width = size.width.min(area.size.width);
+    let height = size.height.min(area.size.height);
+    let max_x = area.position.x + (area.size.width - width) as i32;
+    let max_y = area.position.y + (area.size.height - height) as i32;
+    let x = position.x.clamp(area.position.x, max_x);
+    let y = position.y.clamp(area.position.y, max_y);
+
+    if (width, height) != (size.width, size.height) {
+        // outer_size includes decorations; shrink the inner size by the same amount
+        let inner = window.inner_size()?;
+        window.set_size(PhysicalSize::new(
+            inner.width.saturating_sub(size.width - width),
+            inner.height.saturating_sub(size.height - height),
+        ))?;
+    }
+    if (x, y) != (position.x, position.y) {
+        log::info!("Moving main window back on screen");
+        window.set_position(PhysicalPosition::new(x, y))?;
+    }
+
+    Ok(())
+}
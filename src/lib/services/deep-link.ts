@@ -0,0 +1,32 @@
+/**
+ * Deep link service
+ *
+ * Receives `invariant://` links, such as `invariant://open?db=...`, whether
+ * they launched the app or were opened while it was running. Desktop only.
+ */
+
+import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+
+export type DeepLinkAction = 'open';
+
+export interface DeepLink {
+  url: string;
+  action: DeepLinkAction;
+  /** Decoded query parameters; `open` links always have `db` */
+  params: Record<string, string>;
+}
+
+/**
+ * Call `handler` for every deep link, including any that arrived before this was called
+ *
+ * Links come from outside the app, so confirm with the user before acting on one.
+ * Call once at startup; returns a function that stops listening.
+ */
+export async function onDeepLink(handler: (link: DeepLink) => void): Promise<UnlistenFn> {
+  // Listen first so no link falls between the queue and the events
+  const unlisten = await listen<DeepLink>('deep-link', (event) => handler(event.payload));
+  const pending = await invoke<DeepLink[]>('take_pending_deep_links');
+  pending.forEach(handler);
+  return unlisten;
+}
@@ -0,0 +1,23 @@
+import { invoke } from '@tauri-apps/api/core';
+
+/** Level of the backend log file; 'warn' by default in release builds */
+export type BackendLogLevel = 'off' | 'error' | 'warn' | 'info' | 'debug' | 'trace';
+
+/**
+ * Path of the current backend log file, for attaching to a bug report
+ * Older rotated files sit next to it with a date in their name.
+ */
+export async function getLogPath(): Promise<string> {
+  return invoke<string>('get_log_path');
+}
+
+export async function getLogLevel(): Promise<BackendLogLevel> {
+  return invoke<BackendLogLevel>('get_log_level');
+}
+
+/**
+ * Change the backend log level; it applies immediately and is kept across restarts
+ */
+export async function setLogLevel(level: BackendLogLevel): Promise<void> {
+  return invoke('set_log_level', { level });
+}
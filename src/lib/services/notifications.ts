@@ -0,0 +1,17 @@
+import { invoke } from '@tauri-apps/api/core';
+
+/**
+ * Whether system notifications are on (the default)
+ * They are shown when an update is downloaded or installed without restarting,
+ * and when an import of 10 seconds or more finishes.
+ */
+export async function getNotificationsEnabled(): Promise<boolean> {
+  return invoke<boolean>('get_notifications_enabled');
+}
+
+/**
+ * Turn system notifications on or off; kept across restarts
+ */
+export async function setNotificationsEnabled(enabled: boolean): Promise<void> {
+  return invoke('set_notifications_enabled', { enabled });
+}
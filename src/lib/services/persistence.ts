@@ -1,5 +1,9 @@
 import { getDatabase } from './database';
-import { getCurrentVersion as getCurrentVersionTauri } from './updater';
+import {
+  getCurrentVersion as getCurrentVersionTauri,
+  setChannel,
+  type ReleaseChannel,
+} from './updater';
 import type { SqlParams } from '../utils/sql-types';
 import type {
   Account,
@@ -79,12 +83,14 @@ export class PersistenceService {
     await this.setSetting('mode', mode);
   }
 
-  async getUpdateChannel(): Promise<'stable' | 'beta'> {
+  async getUpdateChannel(): Promise<ReleaseChannel> {
     const channel = await this.getSetting('update_channel');
-    return (channel === 'beta' ? 'beta' : 'stable') as 'stable' | 'beta';
+    return channel || 'stable';
   }
 
-  async setUpdateChannel(channel: 'stable' | 'beta'): Promise<void> {
+  async setUpdateChannel(channel: ReleaseChannel): Promise<void> {
+    // The updater keeps its own copy for background checks
+    await setChannel(channel);
     await this.setSetting('update_channel', channel);
   }
 
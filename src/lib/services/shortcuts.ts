@@ -0,0 +1,37 @@
+/**
+ * Global shortcut service
+ *
+ * Shortcuts that work while another app has focus, for showing the window and
+ * checking for updates. None are set until the user picks one. Desktop only.
+ */
+
+import { invoke } from '@tauri-apps/api/core';
+
+export type ShortcutAction = 'showWindow' | 'checkForUpdates';
+
+export interface GlobalShortcut {
+  action: ShortcutAction;
+  /** The saved accelerator, e.g. 'CmdOrCtrl+Shift+I'; null when unset */
+  accelerator: string | null;
+  /** False when the saved shortcut couldn't be registered */
+  registered: boolean;
+  /** Why registering the saved shortcut failed at startup, e.g. another app holds it */
+  error: string | null;
+}
+
+export async function getGlobalShortcuts(): Promise<GlobalShortcut[]> {
+  return invoke<GlobalShortcut[]>('get_global_shortcuts');
+}
+
+/**
+ * Change an action's shortcut; it applies immediately and is kept across restarts
+ *
+ * Rejects with a message when the shortcut is invalid or already taken, keeping the
+ * previous one. Pass null to remove the shortcut.
+ */
+export async function setGlobalShortcut(
+  action: ShortcutAction,
+  accelerator: string | null
+): Promise<void> {
+  return invoke('set_global_shortcut', { action, accelerator });
+}
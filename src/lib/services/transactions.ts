@@ -1,22 +1,154 @@
 import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
 import { appDataDir } from '@tauri-apps/api/path';
-import type { SqlParams } from '../utils/sql-types';
+import type { SqlValue } from '../utils/sql-types';
+
+/**
+ * A value bound to a transaction step parameter.
+ * Binary data is passed as `{ $blob: '<base64>' }`, and dates as
+ * `{ $datetime: '<RFC 3339>' }` so they are stored in one canonical UTC format.
+ * Arrays and objects are passed as `{ $json: value }` and stored as JSON text.
+ * `{ $int }`, `{ $real }` and `{ $text }` force the storage class whatever the
+ * JSON type, so `{ $int: '42' }` matches an INTEGER id where `'42'` would not.
+ * null binds SQL NULL, which has no storage class, so it fits any nullable column
+ * (STRICT tables included); the tags other than $json take null too, so nullable
+ * values can keep their tag.
+ */
+export type TransactionParam =
+  | SqlValue
+  | { $blob: string | null }
+  | { $datetime: string | null }
+  | { $json: unknown }
+  | { $int: number | string | null }
+  | { $real: number | string | null }
+  | { $text: string | number | null };
 
 export interface TransactionStep {
   sql: string;
-  params: SqlParams;
+  params?: TransactionParam[];
+  /** Values for `:name` placeholders, keyed by name without the prefix; used instead of `params` */
+  namedParams?: Record<string, TransactionParam>;
+  /**
+   * Run this step in its own SAVEPOINT. If it fails, only this step is rolled back
+   * and the transaction continues; other steps keep all-or-nothing semantics.
+   */
+  savepoint?: boolean;
+  /**
+   * Roll back and fail with a 'conflict' error unless the step changes exactly this
+   * many rows, e.g. 1 for an optimistic `UPDATE ... WHERE id = ? AND version = ?`
+   */
+  expectRowsAffected?: number;
+}
+
+export interface TransactionOptions {
+  /** How many times to retry when the database is busy (default 5) */
+  busyRetries?: number;
+  /** Abort and roll back if the transaction takes longer than this, including retries */
+  timeoutMs?: number;
+  /** 'immediate' takes the write lock at BEGIN so busy errors surface up front (default 'deferred') */
+  txMode?: 'deferred' | 'immediate' | 'exclusive';
+  /** Event to emit TransactionProgress on (default 'db-transaction-progress') */
+  progressEventName?: string;
+  /** Run every step and report its results, then roll back instead of committing */
+  dryRun?: boolean;
+  /** Measure each step, reported as StepResult.durationMicros */
+  timing?: boolean;
+  /**
+   * Run steps that only read (SELECT, VALUES, WITH) concurrently on separate connections,
+   * outside a transaction; a step that writes or uses a savepoint fails the call
+   */
+  parallelReads?: boolean;
+}
+
+/** Payload of progress events, emitted every 100 steps and after the last one */
+export interface TransactionProgress {
+  completed: number;
+  total: number;
+}
+
+export interface StepResult {
+  /** False only for a savepoint step that failed and was rolled back */
+  success: boolean;
+  error: string | null;
+  /** The failure with its kind and SQLite result code, for a failed savepoint step */
+  errorDetail: DbError | null;
+  rowsAffected: number;
+  /** Rowid assigned by an INSERT/REPLACE step, if it inserted anything */
+  lastInsertRowid: number | null;
+  /** How long the step took; null unless the transaction ran with `timing` */
+  durationMicros: number | null;
+}
+
+/**
+ * Error returned by the database commands
+ * Branch on `code` rather than the message, which differs between SQLite versions.
+ * `code` is SQLite's extended result code, e.g. 2067 for a UNIQUE constraint,
+ * `primaryCode` the primary code it refines (19, SQLITE_CONSTRAINT) and
+ * `codeName` the extended code's name, e.g. 'SQLITE_CONSTRAINT_UNIQUE'.
+ */
+export interface DbError {
+  kind:
+    | 'connect'
+    | 'begin'
+    | 'bind'
+    | 'execute'
+    | 'conflict'
+    | 'commit'
+    | 'unsupported'
+    | 'invalid'
+    | 'busy'
+    | 'timeout'
+    | 'internal';
+  message: string;
+  /** Zero-based index of the transaction step the error happened in */
+  step: number | null;
+  code: number | null;
+  primaryCode: number | null;
+  codeName: string | null;
+  /** For 'busy', how many times the step was retried before giving up */
+  retries: number | null;
+}
+
+/** Whether an error thrown by a database command is a DbError */
+export function isDbError(error: unknown): error is DbError {
+  return typeof error === 'object' && error !== null && 'kind' in error && 'message' in error;
 }
 
 export interface TransactionResult {
   success: boolean;
   error?: string;
+  /** The failure with its kind and SQLite result code, when the transaction failed */
+  errorDetail?: DbError | null;
+  /** Rows produced by each step (empty for non-SELECT steps); BLOBs are base64 strings */
+  results?: SqlValue[][][];
+  /** Column names for each step's rows */
+  columns?: string[][];
+  /** Declared type of each column ('INTEGER', 'TEXT', 'DATETIME', ...; 'NULL' for expressions) */
+  columnTypes?: string[][];
+  /** Write statistics for each step */
+  steps?: StepResult[];
+  /** Zero-based index of the step that failed; the whole transaction was rolled back */
+  failedStep?: number | null;
+  /**
+   * Steps that ran before the transaction committed, failed or was cancelled, e.g. for
+   * "failed after 700/1000 steps"; informational, since a failure still rolls back everything
+   */
+  completedSteps?: number;
+  /** The transaction was cancelled with cancelTransaction and rolled back */
+  cancelled?: boolean;
 }
 
 /**
  * Execute multiple SQL statements in an atomic transaction
  * All statements succeed or all fail (rollback)
+ *
+ * Pass a `transactionId` to be able to stop it with cancelTransaction.
  */
-export async function executeTransaction(steps: TransactionStep[]): Promise<TransactionResult> {
+export async function executeTransaction(
+  steps: TransactionStep[],
+  options?: TransactionOptions,
+  transactionId?: string,
+): Promise<TransactionResult> {
   try {
     const appDataPath = await appDataDir();
     const dbUrl = `sqlite:${appDataPath}/invariant.db`;
@@ -24,13 +156,71 @@ export async function executeTransaction(steps: TransactionStep[]): Promise<Tran
     const result = await invoke<TransactionResult>('execute_transaction', {
       dbUrl,
       steps,
+      options,
+      transactionId,
     });
 
     return result;
   } catch (error) {
+    if (isDbError(error)) {
+      return { success: false, error: error.message, errorDetail: error };
+    }
     return {
       success: false,
       error: error instanceof Error ? error.message : String(error),
     };
   }
 }
+
+/**
+ * Cancel a transaction started with the given transactionId
+ * Returns false if it is not running (for example, it already committed)
+ */
+export async function cancelTransaction(transactionId: string): Promise<boolean> {
+  return invoke<boolean>('cancel_transaction', { transactionId });
+}
+
+/**
+ * Run transactions on the pool the SQL plugin opened for the app database
+ * instead of a second pool, so the two don't fight over the write lock
+ * Call it after Database.load() and before the first transaction; custom
+ * SQL functions and setup statements aren't available on the plugin's pool.
+ */
+export async function sharePluginPool(): Promise<void> {
+  const appDataPath = await appDataDir();
+  await invoke('share_plugin_pool', { dbUrl: `sqlite:${appDataPath}/invariant.db` });
+}
+
+/**
+ * Show the app database in Finder, Explorer or the system file manager, with the file selected
+ * Desktop only.
+ */
+export async function revealDatabase(): Promise<void> {
+  const appDataPath = await appDataDir();
+  await invoke('reveal_database', { dbUrl: `sqlite:${appDataPath}/invariant.db` });
+}
+
+/**
+ * Send transactions that only read the app database to a replica file, such as a
+ * periodically synced copy; writes still go to the app database. Pass null to stop.
+ * The replica only has what its last sync copied, so reads may miss recent writes;
+ * if it can't be opened, reads fall back to the app database.
+ */
+export async function setReadReplica(replicaUrl: string | null): Promise<void> {
+  const appDataPath = await appDataDir();
+  await invoke('set_read_replica', { dbUrl: `sqlite:${appDataPath}/invariant.db`, replicaUrl });
+}
+
+/** Payload of 'db-changed', emitted after a transaction or batch commits writes */
+export interface DbChanged {
+  dbUrl: string;
+  /** Tables the statements wrote to or altered, as named in the SQL */
+  tables: string[];
+}
+
+/**
+ * Call `callback` whenever any window commits a write, so cached data can be refreshed
+ */
+export async function onDbChanged(callback: (change: DbChanged) => void): Promise<UnlistenFn> {
+  return listen<DbChanged>('db-changed', (event) => callback(event.payload));
+}
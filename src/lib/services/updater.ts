@@ -2,11 +2,11 @@
  * Update service
  *
  * Frontend service for checking and installing application updates.
- * Supports stable and beta release channels.
+ * Supports stable, beta, nightly and alpha release channels, plus custom channels.
  */
 
 import { invoke } from '@tauri-apps/api/core';
-import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import { emit, listen, type UnlistenFn } from '@tauri-apps/api/event';
 import { logger } from '../utils/logger';
 
 export interface UpdateMetadata {
@@ -14,31 +14,171 @@ export interface UpdateMetadata {
   currentVersion: string;
   date?: string;
   body?: string;
+  /** The user skipped this version (or a newer one) with skipVersion */
+  skipped: boolean;
+  /** SHA-256 of the package (hex) from the manifest, checked after download */
+  checksum: string | null;
+}
+
+/** Payload of the event emitted just before the app restarts into an update */
+export interface UpdateRestarting {
+  version: string;
+  /** How long the restart waits for the callback to finish before going ahead */
+  gracePeriodMs: number;
+}
+
+export interface ReleaseNote {
+  version: string;
+  date: string | null;
+  body: string | null;
 }
 
 export interface DownloadProgress {
   downloaded: number;
   contentLength: number | null;
+  bytesPerSecond: number;
+  etaSeconds: number | null;
+  /** The download is complete and its signature and checksum are being checked */
+  verifying?: boolean;
 }
 
 export type DownloadEvent =
-  | { event: 'Started'; data: { contentLength: number | null } }
-  | { event: 'Progress'; data: { chunkLength: number } }
-  | { event: 'Finished'; data: null };
+  | {
+      event: 'Started';
+      /** `resumedFrom` is the number of bytes kept from an interrupted download */
+      data: { contentLength: number | null; resumedFrom: number };
+    }
+  | {
+      event: 'Progress';
+      /**
+       * Sent at most every 100ms; `chunkLength` is the bytes received since the
+       * previous one. `downloaded` is the running total; `total` and `etaSeconds`
+       * are null if the size is unknown. Speed and ETA are averaged over the whole
+       * download.
+       */
+      data: {
+        chunkLength: number;
+        downloaded: number;
+        total: number | null;
+        bytesPerSecond: number;
+        etaSeconds: number | null;
+      };
+    }
+  | { event: 'Verifying'; data: null }
+  | { event: 'Finished'; data: null }
+  | { event: 'Cancelled'; data: null };
+
+/** Built-in channels; any other name is a custom channel published as `latest-<name>` */
+export type ReleaseChannel = 'stable' | 'beta' | 'nightly' | 'alpha' | (string & {});
+
+/**
+ * Error returned by the updater commands
+ * Branch on `kind` rather than the message, which is meant for people.
+ */
+export interface UpdaterError {
+  kind:
+    | 'updater'
+    | 'unreachable'
+    | 'timeout'
+    | 'invalidManifest'
+    | 'invalidChannel'
+    | 'invalidProxy'
+    | 'invalidEndpoint'
+    | 'invalidVersion'
+    | 'download'
+    | 'releases'
+    | 'versionNotFound'
+    | 'settings'
+    | 'noPendingUpdate'
+    | 'noStagedUpdate'
+    | 'cancelled'
+    | 'checksumMismatch'
+    | 'downgradeNotAllowed';
+  message: string;
+}
+
+/** Whether an error thrown by an updater function is an UpdaterError */
+export function isUpdaterError(error: unknown): error is UpdaterError {
+  return typeof error === 'object' && error !== null && 'kind' in error && 'message' in error;
+}
+
+/** The message of an error thrown by an updater function, for showing to the user */
+export function updaterErrorMessage(error: unknown): string {
+  if (isUpdaterError(error)) return error.message;
+  return error instanceof Error ? error.message : String(error);
+}
+
+function hasKind(error: unknown, kind: UpdaterError['kind']): boolean {
+  return isUpdaterError(error) && error.kind === kind;
+}
+
+/**
+ * Whether an update check failed because the update server couldn't be reached,
+ * as opposed to there being no update
+ */
+export function isUnreachableError(error: unknown): boolean {
+  return hasKind(error, 'unreachable');
+}
+
+/**
+ * Whether an update check gave up because the update server didn't respond in time
+ */
+export function isTimeoutError(error: unknown): boolean {
+  return hasKind(error, 'timeout');
+}
 
-export type ReleaseChannel = 'stable' | 'beta';
+/**
+ * Whether an update check failed because the update server returned invalid data,
+ * such as a latest.json that isn't valid JSON or has no entry for this platform
+ */
+export function isInvalidManifestError(error: unknown): boolean {
+  return hasKind(error, 'invalidManifest');
+}
+
+/**
+ * Whether an update check found only a release older than the installed version,
+ * which is not offered unless allowDowngrade is set
+ */
+export function isDowngradeError(error: unknown): boolean {
+  return hasKind(error, 'downgradeNotAllowed');
+}
+
+/**
+ * Whether an update check failed because a configured update endpoint isn't a valid URL
+ */
+export function isInvalidEndpointError(error: unknown): boolean {
+  return hasKind(error, 'invalidEndpoint');
+}
+
+/**
+ * Whether this build can update itself; false on mobile, where the other
+ * functions here aren't available
+ */
+export async function supportsUpdates(): Promise<boolean> {
+  return invoke<boolean>('supports_updates');
+}
 
 /**
  * Check for updates on the specified channel
  *
- * @param channel - Release channel to check (stable or beta)
+ * @param channel - Release channel to check (stable, beta, nightly, alpha or a custom name);
+ *   defaults to the channel saved with setChannel, and otherwise becomes the saved channel
+ * @param endpointOverride - Manifest URL to check instead of the channel's, for self-hosted releases
+ * @param allowDowngrade - Offer the channel's release even if it is older than the installed
+ *   version; otherwise the check fails with an error isDowngradeError recognises
  * @returns Update metadata if an update is available, null otherwise
  */
 export async function checkForUpdate(
-  channel: ReleaseChannel = 'stable',
+  channel?: ReleaseChannel,
+  endpointOverride?: string,
+  allowDowngrade = false,
 ): Promise<UpdateMetadata | null> {
   try {
-    const result = await invoke<UpdateMetadata | null>('check_for_update', { channel });
+    const result = await invoke<UpdateMetadata | null>('check_for_update', {
+      channel,
+      endpointOverride,
+      allowDowngrade,
+    });
     return result;
   } catch (error) {
     logger.error('Failed to check for updates:', error);
@@ -47,20 +187,127 @@ export async function checkForUpdate(
 }
 
 /**
- * Download and install a pending update
+ * Check for a specific version, newer or older than the installed one
  *
- * @param onProgress - Callback to receive download progress events
- * @returns Promise that resolves when download and install is complete
+ * The version's own manifest is used, so it can be installed afterwards with
+ * downloadAndInstallUpdate like any other update, e.g. to roll back.
+ *
+ * @param version - One of the versions from listAvailableVersions
+ * @returns Update metadata for that version, or null if it is already installed
  */
-export async function downloadAndInstallUpdate(
-  onProgress?: (progress: DownloadProgress) => void,
-): Promise<void> {
+export async function checkForVersion(version: string): Promise<UpdateMetadata | null> {
+  try {
+    return await invoke<UpdateMetadata | null>('check_for_update', { version });
+  } catch (error) {
+    logger.error(`Failed to check for version ${version}:`, error);
+    throw error;
+  }
+}
+
+/**
+ * List the versions published on a channel that can be installed with checkForVersion
+ *
+ * Releases are fetched from GitHub and cached for ten minutes, shared with getReleaseNotes.
+ *
+ * @param channel - Channel whose pre-releases to include
+ * @returns Version strings, newest first
+ */
+export async function listAvailableVersions(channel: ReleaseChannel = 'stable'): Promise<string[]> {
+  try {
+    return await invoke<string[]>('list_available_versions', { channel });
+  } catch (error) {
+    logger.error('Failed to list available versions:', error);
+    throw error;
+  }
+}
+
+/**
+ * Get the notes for every release after fromVersion up to and including toVersion
+ *
+ * Releases are fetched from GitHub and cached for ten minutes; only the 100 most recent
+ * are searched.
+ *
+ * @param fromVersion - Version the user is running (not included)
+ * @param toVersion - Newest version to include
+ * @param channel - Channel whose pre-releases to include
+ * @returns Release notes, newest first
+ */
+export async function getReleaseNotes(
+  fromVersion: string,
+  toVersion: string,
+  channel: ReleaseChannel = 'stable',
+): Promise<ReleaseNote[]> {
+  try {
+    return await invoke<ReleaseNote[]>('get_release_notes', { fromVersion, toVersion, channel });
+  } catch (error) {
+    logger.error('Failed to get release notes:', error);
+    throw error;
+  }
+}
+
+/**
+ * The update waiting to be installed, from the last check or a download, without
+ * contacting the update server
+ *
+ * @returns Update metadata if an update is pending or downloaded, null otherwise
+ */
+export async function hasPendingUpdate(): Promise<UpdateMetadata | null> {
+  return invoke<UpdateMetadata | null>('has_pending_update');
+}
+
+/**
+ * Listen for updates found by the background checks
+ *
+ * Background checks are off unless turned on with setAutoUpdateEnabled or
+ * `checkIntervalMinutes` is set in the app's updater.json. The update found
+ * becomes the pending update, so it can be installed with downloadAndInstallUpdate.
+ *
+ * @returns Function that stops listening
+ */
+export async function onUpdateAvailable(
+  callback: (update: UpdateMetadata) => void,
+): Promise<UnlistenFn> {
+  return listen<UpdateMetadata>('update-available', (event) => callback(event.payload));
+}
+
+/**
+ * Run a callback before the app closes to install or restart into an update
+ *
+ * Use it to save unsaved work. The restart waits until the callback's promise
+ * settles, or at most `gracePeriodMs`. On Windows it runs before the installer
+ * starts, since the installer closes the app.
+ *
+ * @returns Function that stops listening
+ */
+export async function onUpdateRestarting(
+  callback: (restart: UpdateRestarting) => void | Promise<void>,
+): Promise<UnlistenFn> {
+  return listen<UpdateRestarting>('update-restarting', async (event) => {
+    try {
+      await callback(event.payload);
+    } catch (error) {
+      logger.error('Failed to prepare for update restart:', error);
+    } finally {
+      await emit('update-restart-ready');
+    }
+  });
+}
+
+/**
+ * Run a download command, passing its progress events to onProgress
+ */
+async function withDownloadProgress<T>(
+  onProgress: ((progress: DownloadProgress) => void) | undefined,
+  run: () => Promise<T>,
+): Promise<T> {
   let unlisten: UnlistenFn | null = null;
 
   try {
     let currentProgress: DownloadProgress = {
       downloaded: 0,
       contentLength: null,
+      bytesPerSecond: 0,
+      etaSeconds: null,
     };
 
     // Listen for download progress events
@@ -70,22 +317,26 @@ export async function downloadAndInstallUpdate(
 
         if (payload.event === 'Started') {
           currentProgress.contentLength = payload.data.contentLength;
+          currentProgress.downloaded = payload.data.resumedFrom;
           onProgress(currentProgress);
         } else if (payload.event === 'Progress') {
-          currentProgress.downloaded += payload.data.chunkLength;
+          currentProgress.downloaded = payload.data.downloaded;
+          currentProgress.contentLength = payload.data.total;
+          currentProgress.bytesPerSecond = payload.data.bytesPerSecond;
+          currentProgress.etaSeconds = payload.data.etaSeconds;
+          onProgress(currentProgress);
+        } else if (payload.event === 'Verifying') {
+          currentProgress.verifying = true;
           onProgress(currentProgress);
         } else if (payload.event === 'Finished') {
           // Download complete
+          currentProgress.verifying = false;
           onProgress(currentProgress);
         }
       });
     }
 
-    // Start the download and installation
-    await invoke('download_and_install_update');
-  } catch (error) {
-    logger.error('Failed to download and install update:', error);
-    throw error;
+    return await run();
   } finally {
     if (unlisten) {
       unlisten();
@@ -93,6 +344,195 @@ export async function downloadAndInstallUpdate(
   }
 }
 
+/**
+ * Download and install a pending update
+ *
+ * An interrupted download is resumed where it stopped the next time this is called,
+ * if the server supports range requests.
+ *
+ * @param onProgress - Callback to receive download progress events
+ * @param restartAfter - Restart into the new version once installed (default true). When false the
+ *   update applies the next time the app starts; call restartApp to apply it sooner. Windows always
+ *   closes the app to install.
+ * @returns Promise that resolves when download and install is complete
+ */
+export async function downloadAndInstallUpdate(
+  onProgress?: (progress: DownloadProgress) => void,
+  restartAfter = true,
+): Promise<void> {
+  try {
+    await withDownloadProgress(onProgress, () =>
+      invoke('download_and_install_update', { restartAfter }),
+    );
+  } catch (error) {
+    logger.error('Failed to download and install update:', error);
+    throw error;
+  }
+}
+
+/**
+ * Download a pending update in the background without installing it
+ *
+ * Call installStagedUpdate when the user is ready to install.
+ *
+ * @param onProgress - Callback to receive download progress events
+ * @returns The update that was downloaded
+ */
+export async function downloadUpdate(
+  onProgress?: (progress: DownloadProgress) => void,
+): Promise<UpdateMetadata> {
+  try {
+    return await withDownloadProgress(onProgress, () => invoke<UpdateMetadata>('download_update'));
+  } catch (error) {
+    logger.error('Failed to download update:', error);
+    throw error;
+  }
+}
+
+/**
+ * Install the update downloaded by downloadUpdate
+ *
+ * @param restartAfter - As for downloadAndInstallUpdate (default true)
+ */
+export async function installStagedUpdate(restartAfter = true): Promise<void> {
+  try {
+    await invoke('install_staged_update', { restartAfter });
+  } catch (error) {
+    logger.error('Failed to install update:', error);
+    throw error;
+  }
+}
+
+/**
+ * Stop offering updates up to and including this version
+ *
+ * The preference is saved, so it lasts across restarts. checkForUpdate still
+ * returns a skipped update but marks it `skipped`.
+ *
+ * @param version - Version to skip
+ */
+export async function skipVersion(version: string): Promise<void> {
+  try {
+    await invoke('skip_version', { version });
+  } catch (error) {
+    logger.error('Failed to skip version:', error);
+    throw error;
+  }
+}
+
+/**
+ * Get the release channel saved with setChannel ('stable' if none was chosen)
+ */
+export async function getCurrentChannel(): Promise<ReleaseChannel> {
+  return invoke<ReleaseChannel>('get_current_channel');
+}
+
+/**
+ * Choose the release channel update checks use by default, including background checks
+ *
+ * @param channel - Letters, digits, '-', '_' and '.' only
+ */
+export async function setChannel(channel: ReleaseChannel): Promise<void> {
+  try {
+    await invoke('set_channel', { channel });
+  } catch (error) {
+    logger.error('Failed to set update channel:', error);
+    throw error;
+  }
+}
+
+/**
+ * Whether background update checks are on
+ */
+export async function getAutoUpdateEnabled(): Promise<boolean> {
+  return invoke<boolean>('get_auto_update_enabled');
+}
+
+/**
+ * Turn background update checks on or off; applies immediately and is kept across restarts
+ *
+ * Checks run every `checkIntervalMinutes` from updater.json, or every 6 hours if
+ * it isn't set. While off, nothing contacts the update server except checkForUpdate.
+ */
+export async function setAutoUpdateEnabled(enabled: boolean): Promise<void> {
+  try {
+    await invoke('set_auto_update_enabled', { enabled });
+  } catch (error) {
+    logger.error('Failed to set auto-update:', error);
+    throw error;
+  }
+}
+
+/**
+ * Set fallback manifest URLs for a release channel
+ *
+ * Checks try the channel's usual endpoint first, then each mirror in order until
+ * one responds with a valid manifest. The mirrors are saved across restarts.
+ *
+ * @param mirrors - Manifest URLs in the order to try them, or [] to remove them
+ */
+export async function setUpdateMirrors(channel: ReleaseChannel, mirrors: string[]): Promise<void> {
+  try {
+    await invoke('set_update_mirrors', { channel, mirrors });
+  } catch (error) {
+    logger.error('Failed to set update mirrors:', error);
+    throw error;
+  }
+}
+
+/**
+ * Set the proxy used to check for and download updates
+ *
+ * @param proxy - e.g. 'http://proxy.example.com:8080', or null to use the
+ *   HTTP_PROXY/HTTPS_PROXY environment variables and system proxy settings
+ */
+export async function setUpdateProxy(proxy: string | null): Promise<void> {
+  try {
+    await invoke('set_update_proxy', { proxy });
+  } catch (error) {
+    logger.error('Failed to set update proxy:', error);
+    throw error;
+  }
+}
+
+/**
+ * Cancel the download started by downloadAndInstallUpdate
+ *
+ * The pending download rejects with "update cancelled"; the update stays
+ * pending, so downloadAndInstallUpdate can be called again to retry.
+ */
+export async function cancelUpdate(): Promise<void> {
+  try {
+    await invoke('cancel_update');
+  } catch (error) {
+    logger.error('Failed to cancel update:', error);
+    throw error;
+  }
+}
+
+/**
+ * Compare two semantic versions (a leading "v" is allowed)
+ *
+ * @returns -1 if a is older than b, 0 if they are the same, 1 if a is newer
+ */
+export async function compareVersions(a: string, b: string): Promise<-1 | 0 | 1> {
+  return invoke<-1 | 0 | 1>('compare_versions', { a, b });
+}
+
+/**
+ * Whether candidate is a newer version than current
+ */
+export async function isNewer(candidate: string, current: string): Promise<boolean> {
+  return invoke<boolean>('is_newer', { candidate, current });
+}
+
+/**
+ * Restart the application, applying an update installed with restartAfter = false
+ */
+export async function restartApp(): Promise<void> {
+  await invoke('restart_app');
+}
+
 /**
  * Get the current application version
  *